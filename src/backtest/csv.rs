@@ -0,0 +1,218 @@
+//! Reading and writing CSV with a header row.
+//!
+//! Every row is one point. The `time` column holds the timestamp, either in
+//! nanoseconds since the epoch or as an RFC 3339 date, and the ticker column is
+//! read as a tag. All other columns are read as fields: numbers as double
+//! fields, anything else as string fields, and empty cells are skipped.
+
+use super::BacktestError;
+use kapacitor_udf::proto::Point;
+use std::{
+    collections::BTreeSet,
+    io::{Read, Write},
+    time::UNIX_EPOCH,
+};
+
+/// Name of the column holding the timestamp.
+pub const TIME_COLUMN: &str = "time";
+
+/// Reads all points from CSV.
+///
+/// # Arguments
+///
+/// * `measurement` - The measurement name given to every point.
+/// * `ticker_field` - The column read as the ticker tag.
+pub fn read(
+    reader: impl Read,
+    measurement: &str,
+    ticker_field: &str,
+) -> Result<Vec<Point>, BacktestError> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .trim(::csv::Trim::All)
+        .from_reader(reader);
+    let headers = reader.headers()?.clone();
+    if !headers.iter().any(|header| header == TIME_COLUMN) {
+        return Err(BacktestError::Parse {
+            line: 1,
+            message: format!("missing {:?} column", TIME_COLUMN),
+        });
+    }
+
+    let mut points = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let line = i + 2;
+        let mut point = Point {
+            name: measurement.to_string(),
+            ..Default::default()
+        };
+
+        for (header, value) in headers.iter().zip(record.iter()) {
+            if header == TIME_COLUMN {
+                point.time =
+                    parse_time(value).map_err(|message| BacktestError::Parse { line, message })?;
+            } else if value.is_empty() {
+                continue;
+            } else if header == ticker_field {
+                point.tags.insert(header.to_string(), value.to_string());
+            } else if let Ok(double) = value.parse() {
+                point.fields_double.insert(header.to_string(), double);
+            } else {
+                point
+                    .fields_string
+                    .insert(header.to_string(), value.to_string());
+            }
+        }
+
+        points.push(point);
+    }
+
+    Ok(points)
+}
+
+/// Writes points as CSV.
+///
+/// The columns are `time` in nanoseconds, followed by all tags and then all
+/// fields of any point, each sorted by name.
+pub fn write(writer: impl Write, points: &[Point]) -> Result<(), BacktestError> {
+    let tags: BTreeSet<&String> = points.iter().flat_map(|p| p.tags.keys()).collect();
+    let fields: BTreeSet<&String> = points
+        .iter()
+        .flat_map(|p| {
+            p.fields_double
+                .keys()
+                .chain(p.fields_int.keys())
+                .chain(p.fields_string.keys())
+                .chain(p.fields_bool.keys())
+        })
+        .collect();
+
+    let mut writer = ::csv::Writer::from_writer(writer);
+    writer.write_record(
+        std::iter::once(TIME_COLUMN)
+            .chain(tags.iter().map(|tag| tag.as_str()))
+            .chain(fields.iter().map(|field| field.as_str())),
+    )?;
+
+    for point in points {
+        let mut record = vec![point.time.to_string()];
+        record.extend(
+            tags.iter()
+                .map(|tag| point.tags.get(*tag).cloned().unwrap_or_default()),
+        );
+        record.extend(fields.iter().map(|field| field_value(point, field)));
+        writer.write_record(&record)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn parse_time(value: &str) -> Result<i64, String> {
+    if let Ok(nanos) = value.parse() {
+        return Ok(nanos);
+    }
+    let time = humantime::parse_rfc3339_weak(value)
+        .map_err(|e| format!("invalid time {:?}: {}", value, e))?;
+    let nanos = time
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| format!("time {:?} is before 1970", value))?
+        .as_nanos();
+    i64::try_from(nanos).map_err(|_| format!("time {:?} is out of range", value))
+}
+
+fn field_value(point: &Point, field: &str) -> String {
+    if let Some(value) = point.fields_double.get(field) {
+        value.to_string()
+    } else if let Some(value) = point.fields_int.get(field) {
+        value.to_string()
+    } else if let Some(value) = point.fields_string.get(field) {
+        value.clone()
+    } else if let Some(value) = point.fields_bool.get(field) {
+        value.to_string()
+    } else {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(input: &str) -> (usize, String) {
+        match read(input.as_bytes(), "m", "ticker") {
+            Err(BacktestError::Parse { line, message }) => (line, message),
+            other => panic!("expected a parse error for {:?}, got {:?}", input, other),
+        }
+    }
+
+    #[test]
+    fn reads_rows_as_points() {
+        let input = concat!(
+            "time,ticker,close,note\n",
+            "1000,A,1.5,\n",
+            "2024-01-02T00:00:00Z, B ,2,\"up, then down\"\n",
+        );
+        let points = read(input.as_bytes(), "stocks", "ticker").unwrap();
+
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].name, "stocks");
+        assert_eq!(points[0].time, 1000);
+        assert_eq!(points[0].tags["ticker"], "A");
+        assert_eq!(points[0].fields_double["close"], 1.5);
+        assert!(points[0].fields_string.is_empty());
+        assert_eq!(points[1].time, 1_704_153_600_000_000_000);
+        assert_eq!(points[1].tags["ticker"], "B");
+        assert_eq!(points[1].fields_double["close"], 2.0);
+        assert_eq!(points[1].fields_string["note"], "up, then down");
+    }
+
+    #[test]
+    fn reports_the_line_of_bad_input() {
+        let (line, message) = parse_error("ticker,close\nA,1\n");
+        assert_eq!((line, message.as_str()), (1, "missing \"time\" column"));
+
+        let (line, message) = parse_error("time,ticker\n1,A\nyesterday,A\n");
+        assert_eq!(line, 3);
+        assert!(message.starts_with("invalid time"), "{}", message);
+
+        assert!(matches!(
+            read("time,a\n1,2,3\n".as_bytes(), "m", "ticker"),
+            Err(BacktestError::Csv(_))
+        ));
+    }
+
+    #[test]
+    fn written_points_read_back() {
+        let points = [
+            Point {
+                name: "m".to_string(),
+                time: 1,
+                tags: [("ticker".to_string(), "A".to_string())].into(),
+                fields_double: [("close".to_string(), 1.5)].into(),
+                fields_string: [("note".to_string(), "a, \"b\"".to_string())].into(),
+                ..Default::default()
+            },
+            Point {
+                name: "m".to_string(),
+                time: 2,
+                tags: [("ticker".to_string(), "B".to_string())].into(),
+                fields_int: [("volume".to_string(), 7)].into(),
+                ..Default::default()
+            },
+        ];
+
+        let mut output = Vec::new();
+        write(&mut output, &points).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert_eq!(
+            text,
+            "time,ticker,close,note,volume\n1,A,1.5,\"a, \"\"b\"\"\",\n2,B,,,7\n"
+        );
+
+        let read_back = read(text.as_bytes(), "m", "ticker").unwrap();
+        assert_eq!(read_back[0], points[0]);
+        // Integers are read back as doubles
+        assert_eq!(read_back[1].fields_double["volume"], 7.0);
+    }
+}
@@ -0,0 +1,331 @@
+//! Reading and writing InfluxDB line protocol.
+//!
+//! Only nanosecond timestamps are supported, and every line must carry one,
+//! since the indicators depend on the time order of the points.
+
+use super::BacktestError;
+use kapacitor_udf::proto::Point;
+use std::io::{BufRead, BufReader, Read, Write};
+use tracing::warn;
+
+/// Characters escaped in measurement names.
+const MEASUREMENT_SPECIAL: &str = ", ";
+/// Characters escaped in tag keys, tag values and field keys.
+const KEY_SPECIAL: &str = ",= ";
+/// Characters escaped in string field values.
+const STRING_SPECIAL: &str = "\"\\";
+
+/// Reads all points from line protocol, skipping blank lines and comments.
+pub fn read(reader: impl Read) -> Result<Vec<Point>, BacktestError> {
+    let mut points = Vec::new();
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let point = parse_line(line).map_err(|message| BacktestError::Parse {
+            line: i + 1,
+            message,
+        })?;
+        points.push(point);
+    }
+    Ok(points)
+}
+
+/// Writes points as line protocol, one line per point.
+///
+/// Fields are written sorted by type and name. Points without any field that
+/// line protocol can represent are skipped with a warning.
+pub fn write(mut writer: impl Write, points: &[Point]) -> Result<(), BacktestError> {
+    for point in points {
+        match format_line(point) {
+            Some(line) => writeln!(writer, "{}", line)?,
+            None => warn!("Skipping point without fields at time {}", point.time),
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn parse_line(line: &str) -> Result<Point, String> {
+    let sections = split_unescaped(line, ' ', true);
+    let (series, fields, time) = match sections.as_slice() {
+        [series, fields, time] => (*series, *fields, *time),
+        [_, _] => return Err("missing timestamp".to_string()),
+        _ => return Err("expected measurement, fields and timestamp".to_string()),
+    };
+
+    let mut point = Point {
+        time: time
+            .parse()
+            .map_err(|e| format!("invalid timestamp {:?}: {}", time, e))?,
+        ..Default::default()
+    };
+
+    let mut series = split_unescaped(series, ',', false).into_iter();
+    point.name = unescape(series.next().unwrap_or_default(), MEASUREMENT_SPECIAL);
+    if point.name.is_empty() {
+        return Err("missing measurement".to_string());
+    }
+    for tag in series {
+        let (key, value) = split_pair(tag)?;
+        point
+            .tags
+            .insert(unescape(key, KEY_SPECIAL), unescape(value, KEY_SPECIAL));
+    }
+
+    for field in split_unescaped(fields, ',', true) {
+        let (key, value) = split_pair(field)?;
+        let key = unescape(key, KEY_SPECIAL);
+        if let Some(quoted) = value.strip_prefix('"') {
+            let string = quoted
+                .strip_suffix('"')
+                .ok_or_else(|| format!("unterminated string in field {:?}", key))?;
+            point
+                .fields_string
+                .insert(key, unescape(string, STRING_SPECIAL));
+        } else if let Some(int) = value.strip_suffix('i').or_else(|| value.strip_suffix('u')) {
+            let int = int
+                .parse()
+                .map_err(|e| format!("invalid integer in field {:?}: {}", key, e))?;
+            point.fields_int.insert(key, int);
+        } else if let Some(bool) = parse_bool(value) {
+            point.fields_bool.insert(key, bool);
+        } else {
+            let double = value
+                .parse()
+                .map_err(|e| format!("invalid float in field {:?}: {}", key, e))?;
+            point.fields_double.insert(key, double);
+        }
+    }
+
+    Ok(point)
+}
+
+fn format_line(point: &Point) -> Option<String> {
+    let mut fields = Vec::new();
+    let mut doubles: Vec<_> = point
+        .fields_double
+        .iter()
+        .filter(|(_, value)| value.is_finite())
+        .collect();
+    doubles.sort_by(|a, b| a.0.cmp(b.0));
+    fields.extend(
+        doubles
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", escape(key, KEY_SPECIAL), value)),
+    );
+    let mut ints: Vec<_> = point.fields_int.iter().collect();
+    ints.sort();
+    fields.extend(
+        ints.into_iter()
+            .map(|(key, value)| format!("{}={}i", escape(key, KEY_SPECIAL), value)),
+    );
+    let mut strings: Vec<_> = point.fields_string.iter().collect();
+    strings.sort();
+    fields.extend(strings.into_iter().map(|(key, value)| {
+        format!(
+            "{}=\"{}\"",
+            escape(key, KEY_SPECIAL),
+            escape(value, STRING_SPECIAL)
+        )
+    }));
+    let mut bools: Vec<_> = point.fields_bool.iter().collect();
+    bools.sort();
+    fields.extend(
+        bools
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", escape(key, KEY_SPECIAL), value)),
+    );
+    if fields.is_empty() {
+        return None;
+    }
+
+    let mut tags: Vec<_> = point.tags.iter().collect();
+    tags.sort();
+    let mut series = escape(&point.name, MEASUREMENT_SPECIAL);
+    for (key, value) in tags {
+        series.push_str(&format!(
+            ",{}={}",
+            escape(key, KEY_SPECIAL),
+            escape(value, KEY_SPECIAL)
+        ));
+    }
+
+    Some(format!("{} {} {}", series, fields.join(","), point.time))
+}
+
+/// Splits on `separator` where it is not escaped with a backslash and, if
+/// `quotes` is set, not inside a double-quoted string.
+fn split_unescaped(s: &str, separator: char, quotes: bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    let mut quoted = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' && quotes {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&s[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn split_pair(s: &str) -> Result<(&str, &str), String> {
+    match split_unescaped(s, '=', false).as_slice() {
+        [key, _, ..] if !key.is_empty() => Ok((key, &s[key.len() + 1..])),
+        _ => Err(format!("expected key=value, got {:?}", s)),
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "t" | "T" | "true" | "True" | "TRUE" => Some(true),
+        "f" | "F" | "false" | "False" | "FALSE" => Some(false),
+        _ => None,
+    }
+}
+
+/// Removes the backslashes escaping any of the `special` characters.
+fn unescape(s: &str, special: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next) if c == '\\' && special.contains(next) => {
+                unescaped.push(next);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Escapes the `special` characters with a backslash.
+fn escape(s: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(input: &str) -> (usize, String) {
+        match read(input.as_bytes()) {
+            Err(BacktestError::Parse { line, message }) => (line, message),
+            other => panic!("expected a parse error for {:?}, got {:?}", input, other),
+        }
+    }
+
+    #[test]
+    fn reads_escapes_and_field_types() {
+        let input = concat!(
+            "# a comment\n",
+            "\n",
+            r#"my\ quotes\,daily,ticker=BRK\ A,k\=ey=v\,al close=1.5,volume=10i,count=3u,"#,
+            r#"note="say \"hi\", then \\ leave",flag=t 1700000000000000000"#,
+            "\n",
+        );
+        let points = read(input.as_bytes()).unwrap();
+
+        assert_eq!(points.len(), 1);
+        let point = &points[0];
+        assert_eq!(point.name, "my quotes,daily");
+        assert_eq!(point.time, 1_700_000_000_000_000_000);
+        assert_eq!(point.tags["ticker"], "BRK A");
+        assert_eq!(point.tags["k=ey"], "v,al");
+        assert_eq!(point.fields_double["close"], 1.5);
+        assert_eq!(point.fields_int["volume"], 10);
+        assert_eq!(point.fields_int["count"], 3);
+        assert_eq!(point.fields_string["note"], r#"say "hi", then \ leave"#);
+        assert!(point.fields_bool["flag"]);
+    }
+
+    #[test]
+    fn reports_the_line_of_bad_input() {
+        assert_eq!(
+            parse_error("m f=1 1\n\nm f=1\n"),
+            (3, "missing timestamp".to_string())
+        );
+
+        for (input, message) in [
+            ("m f=1 1 2", "expected measurement, fields and timestamp"),
+            ("m f=1 1.5", "invalid timestamp"),
+            (",t=a f=1 1", "missing measurement"),
+            ("m,t f=1 1", "expected key=value"),
+            ("m f 1", "expected key=value"),
+            ("m =1 1", "expected key=value"),
+            // The unterminated quote swallows the separator before the timestamp
+            (r#"m f="open 1"#, "missing timestamp"),
+            (r#"m f="a"b 1"#, "unterminated string"),
+            ("m f=1.5i 1", "invalid integer"),
+            ("m f=abc 1", "invalid float"),
+        ] {
+            let (line, actual) = parse_error(input);
+            assert_eq!(line, 1, "{}", input);
+            assert!(
+                actual.starts_with(message),
+                "{:?} gave {:?}, expected {:?}",
+                input,
+                actual,
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn written_points_read_back() {
+        let point = Point {
+            name: "my quotes,daily".to_string(),
+            time: 42,
+            tags: [("ticker".to_string(), "BRK A".to_string())].into(),
+            fields_double: [
+                ("close".to_string(), 1.5),
+                ("skipped".to_string(), f64::NAN),
+            ]
+            .into(),
+            fields_int: [("volume".to_string(), -10)].into(),
+            fields_string: [("note".to_string(), r#"say "hi" \ bye"#.to_string())].into(),
+            fields_bool: [("flag".to_string(), false)].into(),
+            ..Default::default()
+        };
+        let empty = Point {
+            name: "empty".to_string(),
+            fields_double: [("nan".to_string(), f64::NAN)].into(),
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        write(&mut output, &[point.clone(), empty]).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert_eq!(
+            text,
+            concat!(
+                r#"my\ quotes\,daily,ticker=BRK\ A close=1.5,volume=-10i,"#,
+                r#"note="say \"hi\" \\ bye",flag=false 42"#,
+                "\n"
+            )
+        );
+
+        let read_back = read(text.as_bytes()).unwrap();
+        let mut expected = point;
+        expected.fields_double.remove("skipped");
+        assert_eq!(read_back, [expected]);
+    }
+}
@@ -0,0 +1,201 @@
+//! Offline backtesting of indicators over recorded data.
+//!
+//! A backtest reads points from InfluxDB line protocol or CSV, groups them by
+//! the ticker field and feeds every ticker through an `IndicatorHandler` as one
+//! or more batches, exactly as Kapacitor would. The enriched points are
+//! collected from the handler's responses instead of being sent over a socket.
+
+use crate::handler::{
+    config::{IndicatorOptionError, IndicatorOptions},
+    indicator_handler::IndicatorHandler,
+};
+use async_std::{
+    channel,
+    sync::{Arc, Mutex},
+};
+use kapacitor_udf::{
+    proto::{response, BeginBatch, EndBatch, InitRequest, Option as ProtoOption, Point},
+    traits::Handler,
+};
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+};
+use thiserror::Error;
+use tracing::debug;
+
+pub mod csv;
+pub mod line_protocol;
+
+#[derive(Debug, Error)]
+pub enum BacktestError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error("CSV error: {0}")]
+    Csv(#[from] ::csv::Error),
+    #[error("Invalid options: {0}")]
+    Options(#[from] IndicatorOptionError),
+    #[error("Failed to initialize handler: {0}")]
+    Init(String),
+}
+
+/// Text formats points are read from and written to.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    /// InfluxDB line protocol with nanosecond timestamps.
+    #[value(name = "lp")]
+    LineProtocol,
+    /// CSV with a header row and a `time` column.
+    Csv,
+}
+
+/// Reads points in the given format.
+///
+/// `measurement` names the points read from CSV, which has no measurement
+/// column. The `ticker_field` column of a CSV file is read as a tag, all others
+/// as fields.
+pub fn read_points(
+    format: Format,
+    reader: impl Read,
+    measurement: &str,
+    ticker_field: &str,
+) -> Result<Vec<Point>, BacktestError> {
+    match format {
+        Format::LineProtocol => line_protocol::read(reader),
+        Format::Csv => csv::read(reader, measurement, ticker_field),
+    }
+}
+
+/// Writes points in the given format.
+pub fn write_points(
+    format: Format,
+    writer: impl Write,
+    points: &[Point],
+) -> Result<(), BacktestError> {
+    match format {
+        Format::LineProtocol => line_protocol::write(writer, points),
+        Format::Csv => csv::write(writer, points),
+    }
+}
+
+/// Runs an indicator configuration over a set of points.
+#[derive(Debug, Clone)]
+pub struct Backtest {
+    proto_options: Vec<ProtoOption>,
+    options: IndicatorOptions,
+    batch_size: usize,
+}
+
+impl Backtest {
+    /// Creates a new `Backtest` with the options a Kapacitor task would send.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The UDF options, validated the same way as in `init`.
+    pub fn new(options: Vec<ProtoOption>) -> Result<Self, BacktestError> {
+        Ok(Backtest {
+            options: IndicatorOptions::from_proto_options(&options)?,
+            proto_options: options,
+            batch_size: 0,
+        })
+    }
+
+    /// Splits the series of every ticker into batches of at most `batch_size` points.
+    ///
+    /// With the default of 0, every ticker is processed in a single batch.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Returns the parsed indicator options.
+    pub fn options(&self) -> &IndicatorOptions {
+        &self.options
+    }
+
+    /// Processes the points and returns the points the handler emits, ordered by time.
+    ///
+    /// Points are grouped by their ticker tag in order of first appearance;
+    /// points without the tag form a group of their own.
+    pub async fn run(&self, points: Vec<Point>) -> Result<Vec<Point>, BacktestError> {
+        let (sender, receiver) = channel::unbounded();
+        let mut handler =
+            IndicatorHandler::new(Arc::new(Mutex::new(sender)), IndicatorOptions::default()).await;
+
+        let init = handler
+            .init(&InitRequest {
+                options: self.proto_options.clone(),
+                task_id: "backtest".to_string(),
+                node_id: "backtest".to_string(),
+            })
+            .await?;
+        if !init.success {
+            return Err(BacktestError::Init(init.error));
+        }
+
+        let ticker_field = &self.options.ticker_field;
+        let mut groups: Vec<(Option<String>, Vec<Point>)> = Vec::new();
+        let mut index: HashMap<Option<String>, usize> = HashMap::new();
+        for point in points {
+            let ticker = point.tags.get(ticker_field).cloned();
+            let i = *index.entry(ticker.clone()).or_insert_with(|| {
+                groups.push((ticker, Vec::new()));
+                groups.len() - 1
+            });
+            groups[i].1.push(point);
+        }
+
+        let mut output = Vec::new();
+        for (ticker, mut points) in groups {
+            points.sort_by_key(|p| p.time);
+            let batch_size = match self.batch_size {
+                0 => points.len().max(1),
+                n => n,
+            };
+            debug!("Backtesting {} points of ticker {:?}", points.len(), ticker);
+
+            let tags: HashMap<String, String> = ticker
+                .iter()
+                .map(|ticker| (ticker_field.clone(), ticker.clone()))
+                .collect();
+            let group = ticker.clone().unwrap_or_default();
+
+            for batch in points.chunks(batch_size) {
+                let name = batch[0].name.clone();
+                handler
+                    .begin_batch(&BeginBatch {
+                        name: name.clone(),
+                        group: group.clone(),
+                        tags: tags.clone(),
+                        size: batch.len() as i64,
+                        by_name: false,
+                    })
+                    .await?;
+                for point in batch {
+                    handler.point(point).await?;
+                }
+                handler
+                    .end_batch(&EndBatch {
+                        name,
+                        group: group.clone(),
+                        tmax: batch[batch.len() - 1].time,
+                        tags: tags.clone(),
+                        by_name: false,
+                    })
+                    .await?;
+
+                while let Ok(response) = receiver.try_recv() {
+                    if let Some(response::Message::Point(point)) = response.message {
+                        output.push(point);
+                    }
+                }
+            }
+        }
+
+        handler.stop().await;
+        output.sort_by_key(|p| p.time);
+        Ok(output)
+    }
+}
@@ -10,19 +10,39 @@ use async_std::{
 };
 use async_trait::async_trait;
 use kapacitor_udf::{agent::Agent, traits::AccepterTrait};
-use std::sync::atomic::{AtomicI64, Ordering};
+use std::{
+    fs::File,
+    path::PathBuf,
+    sync::atomic::{AtomicI64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
 use tracing::{debug, error, info};
 
-use crate::handler::{config::IndicatorOptions, indicator_handler::IndicatorHandler};
+use crate::{
+    handler::{
+        config::IndicatorOptions, indicator_handler::IndicatorHandler, metrics::Metrics,
+        point_trace::PointTrace, state_store::StateStore,
+    },
+    replay::{recorder::Recorder, REQUESTS_EXTENSION, RESPONSES_EXTENSION},
+};
 
 /// An accepter for new UDF connections.
 ///
-/// This struct keeps track of the number of connections it has accepted
-/// and creates a new agent for each connection.
+/// This struct keeps track of the number of connections it has accepted,
+/// also in its metrics if any, and creates a new agent for each connection.
 #[derive(Debug)]
 pub struct Accepter {
-    /// Counter for the number of connections accepted.
-    count: Arc<AtomicI64>,
+    /// Connections accepted so far, numbering the connections in logs and recordings.
+    connections: AtomicI64,
+    /// Optional store that handlers persist their per-ticker state to.
+    state_store: Option<Arc<dyn StateStore>>,
+    /// Optional metrics shared by the handlers of all accepted connections,
+    /// including the number of connections accepted.
+    metrics: Option<Arc<Metrics>>,
+    /// Optional selection of points whose processing is logged, shared by all handlers.
+    point_trace: Option<Arc<PointTrace>>,
+    /// Optional directory the traffic of every connection is recorded to.
+    record_dir: Option<PathBuf>,
 }
 
 impl Accepter {
@@ -33,9 +53,78 @@ impl Accepter {
     /// A new `Accepter` with the connection count initialized to 0.
     pub fn new() -> Self {
         Accepter {
-            count: Arc::new(AtomicI64::new(0)),
+            connections: AtomicI64::new(0),
+            state_store: None,
+            metrics: None,
+            point_trace: None,
+            record_dir: None,
         }
     }
+
+    /// Sets the metrics shared by the handlers of all accepted connections.
+    ///
+    /// Without metrics, neither the accepter nor the handlers record any.
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - The metrics to report connections and points to.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Sets the state store shared by the handlers of all accepted connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `state_store` - The store to load and flush per-ticker state with.
+    pub fn with_state_store(mut self, state_store: Arc<dyn StateStore>) -> Self {
+        self.state_store = Some(state_store);
+        self
+    }
+
+    /// Sets the selection of points whose processing the handlers log.
+    ///
+    /// # Arguments
+    ///
+    /// * `point_trace` - The tickers and sample rate of traced points.
+    pub fn with_point_trace(mut self, point_trace: Arc<PointTrace>) -> Self {
+        self.point_trace = Some(point_trace);
+        self
+    }
+
+    /// Records the requests and responses of every connection to the given directory.
+    ///
+    /// Each connection is written to a `session-<millis>-<connection>.requests`
+    /// and `.responses` file pair that can be replayed with `replay::Session`.
+    ///
+    /// # Arguments
+    ///
+    /// * `record_dir` - The existing directory to write the recordings to.
+    pub fn with_record_dir(mut self, record_dir: PathBuf) -> Self {
+        self.record_dir = Some(record_dir);
+        self
+    }
+
+    /// Creates the files recording the requests and responses of a connection.
+    fn recording_files(&self, connection: i64) -> Option<(File, File)> {
+        let dir = self.record_dir.as_ref()?;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let base = dir.join(format!("session-{}-{}", millis, connection));
+        let create = |extension| {
+            let path = base.with_extension(extension);
+            File::create(&path)
+                .map_err(|e| error!("Failed to create recording {}: {}", path.display(), e))
+                .ok()
+        };
+
+        let files = (create(REQUESTS_EXTENSION)?, create(RESPONSES_EXTENSION)?);
+        info!("Recording connection {} to {}", connection, base.display());
+        Some(files)
+    }
 }
 
 impl Default for Accepter {
@@ -59,16 +148,35 @@ impl AccepterTrait for Accepter {
     /// * `stream` - The Unix stream for the new connection.
     fn accept(&self, stream: UnixStream) {
         // Increment and get the current connection count
-        let count = self.count.fetch_add(1, Ordering::SeqCst);
+        let count = self.connections.fetch_add(1, Ordering::SeqCst);
+        if let Some(metrics) = &self.metrics {
+            metrics.connections_accepted.fetch_add(1, Ordering::Relaxed);
+        }
         debug!("Accept() called, connection count: {}", count);
 
-        // Create a new agent for this connection
-        let mut agent = Agent::new(Box::new(stream.clone()), Box::new(stream));
+        // Create a new agent for this connection, recording its traffic if requested
+        let mut agent = match self.recording_files(count) {
+            Some((requests, responses)) => Agent::new(
+                Box::new(Recorder::new(stream.clone(), requests)),
+                Box::new(Recorder::new(stream, responses)),
+            ),
+            None => Agent::new(Box::new(stream.clone()), Box::new(stream)),
+        };
         let responses = agent.responses().clone();
 
         // Create and set the handler for this agent
         let options = IndicatorOptions::default(); // You can customize this if needed
-        let handler = Box::new(block_on(IndicatorHandler::new(responses, options)));
+        let mut handler = block_on(IndicatorHandler::new(responses, options));
+        if let Some(metrics) = &self.metrics {
+            handler = handler.with_metrics(Arc::clone(metrics));
+        }
+        if let Some(state_store) = &self.state_store {
+            handler = handler.with_state_store(Arc::clone(state_store));
+        }
+        if let Some(point_trace) = &self.point_trace {
+            handler = handler.with_point_trace(Arc::clone(point_trace));
+        }
+        let handler = Box::new(handler);
         agent.set_handler(Some(handler));
 
         // Wrap the agent in Arc<Mutex<>> for safe sharing across tasks
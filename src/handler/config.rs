@@ -1,4 +1,7 @@
-use kapacitor_udf::proto::{Option as ProtoOption, OptionInfo, OptionValue, ValueType};
+use super::indicators::IndicatorDetail;
+use kapacitor_udf::proto::{
+    option_value::Value, Option as ProtoOption, OptionInfo, OptionValue, ValueType,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use thiserror::Error;
@@ -13,12 +16,143 @@ pub enum IndicatorOptionError {
     UnknownOption(String),
     #[error("Invalid indicator type: {0}")]
     InvalidIndicatorType(String),
+    #[error("Invalid value for '{0}' option: {1}")]
+    InvalidOptionValue(String, String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum IndicatorType {
     EMA,
     SMA,
+    ADX,
+    AROON,
+    SAR,
+    SUPERTREND,
+    KELTNER,
+    DONCHIAN,
+    CCI,
+    ROC,
+    MOM,
+    TRIX,
+    ULTOSC,
+    ICHIMOKU,
+    STDDEV,
+    VARIANCE,
+    ZSCORE,
+    PERCENTRANK,
+    MIN,
+    MAX,
+    MEDIAN,
+    RETURN,
+    LOGRETURN,
+    REALIZEDVOL,
+    PARKINSON,
+    GARMANKLASS,
+    DRAWDOWN,
+    LINREG,
+}
+
+impl IndicatorType {
+    /// Whether the indicator reads the high, low and close fields instead of `field`.
+    pub fn uses_ohlc(&self) -> bool {
+        match self {
+            IndicatorType::EMA
+            | IndicatorType::SMA
+            | IndicatorType::ROC
+            | IndicatorType::MOM
+            | IndicatorType::TRIX
+            | IndicatorType::STDDEV
+            | IndicatorType::VARIANCE
+            | IndicatorType::ZSCORE
+            | IndicatorType::PERCENTRANK
+            | IndicatorType::MIN
+            | IndicatorType::MAX
+            | IndicatorType::MEDIAN
+            | IndicatorType::RETURN
+            | IndicatorType::LOGRETURN
+            | IndicatorType::REALIZEDVOL
+            | IndicatorType::DRAWDOWN
+            | IndicatorType::LINREG => false,
+            IndicatorType::ADX
+            | IndicatorType::AROON
+            | IndicatorType::SAR
+            | IndicatorType::SUPERTREND
+            | IndicatorType::KELTNER
+            | IndicatorType::DONCHIAN
+            | IndicatorType::CCI
+            | IndicatorType::ULTOSC
+            | IndicatorType::ICHIMOKU
+            | IndicatorType::PARKINSON
+            | IndicatorType::GARMANKLASS => true,
+        }
+    }
+
+    /// Whether the indicator also reads the open field.
+    pub fn uses_open(&self) -> bool {
+        *self == IndicatorType::GARMANKLASS
+    }
+}
+
+/// Encoding used for the snapshots handed to Kapacitor.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SnapshotFormat {
+    /// Human-readable JSON.
+    Json,
+    /// Compact MessagePack encoding.
+    Binary,
+}
+
+/// What to do when the time between two points of a ticker exceeds `max_gap`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum GapPolicy {
+    /// Discard the ticker's state and start over with the new point.
+    Reset,
+    /// Ignore the gap and continue from the existing state.
+    Carry,
+    /// Feed the last value once per missing interval before the new point.
+    Fill,
+}
+
+/// The x of the points `LINREG` fits a line through.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum RegressionX {
+    /// The index of the point in the window.
+    Index,
+    /// `Point::time` in seconds.
+    Time,
+}
+
+/// What to do with a point older than the last point processed for its ticker.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum LatePolicy {
+    /// Drop the point.
+    Drop,
+    /// Rewind the ticker and replay the points after the late one, within `replay_window`.
+    Recompute,
+    /// Process the point out of order and log a warning.
+    Warn,
+}
+
+/// What to do when a batch grows beyond `max_batch_points`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum OverflowPolicy {
+    /// Discard the batch and fail the task.
+    Error,
+    /// Process and forward the buffered points as a batch of their own, then continue with the rest.
+    Stream,
+    /// Drop the points beyond the limit and log a warning.
+    Truncate,
+}
+
+/// What to do with a point that lacks the ticker tag or the input field.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum RejectPolicy {
+    /// Drop the point and log a warning.
+    Drop,
+    /// Forward the point unchanged, with the reason in an `<as>_error` string field.
+    Pass,
+    /// Drop the point and count it in a summary point appended to the batch.
+    Summary,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,78 +162,307 @@ pub struct IndicatorOptions {
     pub field: String,
     pub as_field: String,
     pub ticker_field: String,
+    /// Field holding the high of a bar, for indicators on high/low/close.
+    pub high_field: String,
+    pub low_field: String,
+    pub close_field: String,
+    /// Field holding the open of a bar, for `GARMANKLASS`.
+    pub open_field: String,
+    /// Bars per year, by whose square root the volatility indicators are scaled.
+    pub annualization: f64,
+    /// Multiple of the ATR between the price and the bands of `SUPERTREND` and `KELTNER`.
+    pub multiplier: f64,
+    /// Period of the ATR sizing the `KELTNER` bands.
+    pub atr_period: u32,
+    /// Medium period of `ULTOSC` and `ICHIMOKU`, 0 for twice `period`.
+    pub medium_period: u32,
+    /// Long period of `ULTOSC` and `ICHIMOKU`, 0 for four times `period`.
+    pub long_period: u32,
+    /// Number of bars the `ICHIMOKU` spans are shifted by, 0 for the medium period.
+    pub displacement: u32,
+    /// Acceleration factor step of `SAR`, also its initial value.
+    pub acceleration: f64,
+    pub max_acceleration: f64,
+    pub regression_x: RegressionX,
+    /// Number of bars ahead of the latest point the `LINREG` forecast is for.
+    pub forecast: u32,
+    /// Field whose crossings are signaled, empty to disable signals.
+    pub signal_on: String,
+    /// Field `signal_on` is compared with, empty to compare with `signal_threshold`.
+    pub signal_cross: String,
+    pub signal_threshold: f64,
+    /// Write the signal as a tag instead of a string field.
+    pub signal_tag: bool,
+    pub snapshot_format: SnapshotFormat,
+    /// Maximum number of tickers to keep state for, 0 for no limit.
+    pub max_series: usize,
+    /// Idle time in nanoseconds of `Point::time` after which a ticker's state is dropped, 0 to keep it forever.
+    pub series_ttl: i64,
+    /// Largest gap in nanoseconds between two points of a ticker before `gap_policy` applies, 0 to disable.
+    pub max_gap: i64,
+    pub gap_policy: GapPolicy,
+    pub late_policy: LatePolicy,
+    /// Number of recent points per ticker kept to recompute late points.
+    pub replay_window: usize,
+    /// Do not forward batches that contain no points after processing.
+    pub suppress_empty: bool,
+    /// Maximum number of points buffered per batch, 0 for no limit.
+    pub max_batch_points: usize,
+    pub overflow_policy: OverflowPolicy,
+    pub on_reject: RejectPolicy,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct IndicatorState {
     pub current_value: Option<f64>,
     pub values: Vec<f64>,
     pub count: u32,
+    /// Time of the last point processed for the ticker.
+    #[serde(default)]
+    pub last_time: Option<i64>,
+    /// Input value of the last point processed for the ticker.
+    #[serde(default)]
+    pub last_value: Option<f64>,
+    /// Time between the last two points processed for the ticker.
+    #[serde(default)]
+    pub last_interval: Option<i64>,
+    /// State specific to the indicator type, for indicators that need more than the fields above.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<IndicatorDetail>,
+    /// Last non-zero difference between `signal_on` and its reference.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal_difference: Option<f64>,
 }
 
+/// Options accepted by the UDF together with the value type Kapacitor should send for them.
+const OPTION_TYPES: &[(&str, ValueType)] = &[
+    ("type", ValueType::String),
+    ("period", ValueType::Int),
+    ("field", ValueType::String),
+    ("as", ValueType::String),
+    ("ticker_field", ValueType::String),
+    ("high_field", ValueType::String),
+    ("low_field", ValueType::String),
+    ("close_field", ValueType::String),
+    ("open_field", ValueType::String),
+    ("annualization", ValueType::Double),
+    ("multiplier", ValueType::Double),
+    ("atr_period", ValueType::Int),
+    ("medium_period", ValueType::Int),
+    ("long_period", ValueType::Int),
+    ("displacement", ValueType::Int),
+    ("acceleration", ValueType::Double),
+    ("max_acceleration", ValueType::Double),
+    ("regression_x", ValueType::String),
+    ("forecast", ValueType::Int),
+    ("signal_on", ValueType::String),
+    ("signal_cross", ValueType::String),
+    ("signal_threshold", ValueType::Double),
+    ("signal_tag", ValueType::Bool),
+    ("snapshot_format", ValueType::String),
+    ("max_series", ValueType::Int),
+    ("series_ttl", ValueType::Duration),
+    ("max_gap", ValueType::Duration),
+    ("gap_policy", ValueType::String),
+    ("late_policy", ValueType::String),
+    ("replay_window", ValueType::Int),
+    ("suppress_empty", ValueType::Bool),
+    ("max_batch_points", ValueType::Int),
+    ("overflow_policy", ValueType::String),
+    ("on_reject", ValueType::String),
+];
+
 impl IndicatorOptions {
     pub fn from_proto_options(options: &[ProtoOption]) -> Result<Self, IndicatorOptionError> {
         let mut indicator_options = IndicatorOptions::default();
 
         for option in options {
+            let name = option.name.as_str();
             let value = option
                 .values
                 .first()
                 .ok_or_else(|| IndicatorOptionError::MissingOptionValue(option.name.clone()))?;
 
-            match option.name.as_str() {
+            match name {
                 "type" => {
-                    if let Some(kapacitor_udf::proto::option_value::Value::StringValue(ref v)) =
-                        value.value
-                    {
-                        indicator_options.indicator_type = match v.to_uppercase().as_str() {
-                            "EMA" => IndicatorType::EMA,
-                            "SMA" => IndicatorType::SMA,
-                            _ => return Err(IndicatorOptionError::InvalidIndicatorType(v.clone())),
-                        };
-                    } else {
-                        return Err(IndicatorOptionError::InvalidOptionType("type".to_string()));
-                    }
-                }
-                "period" => {
-                    if let Some(kapacitor_udf::proto::option_value::Value::IntValue(v)) =
-                        value.value
-                    {
-                        indicator_options.period = v as u32;
-                    } else {
-                        return Err(IndicatorOptionError::InvalidOptionType(
-                            "period".to_string(),
-                        ));
-                    }
-                }
-                "field" => {
-                    if let Some(kapacitor_udf::proto::option_value::Value::StringValue(ref v)) =
-                        value.value
-                    {
-                        indicator_options.field = v.clone();
-                    } else {
-                        return Err(IndicatorOptionError::InvalidOptionType("field".to_string()));
-                    }
-                }
-                "as" => {
-                    if let Some(kapacitor_udf::proto::option_value::Value::StringValue(ref v)) =
-                        value.value
-                    {
-                        indicator_options.as_field = v.clone();
-                    } else {
-                        return Err(IndicatorOptionError::InvalidOptionType("as".to_string()));
-                    }
-                }
-                "ticker_field" => {
-                    if let Some(kapacitor_udf::proto::option_value::Value::StringValue(ref v)) =
-                        value.value
-                    {
-                        indicator_options.ticker_field = v.clone();
-                    } else {
-                        return Err(IndicatorOptionError::InvalidOptionType(
-                            "ticker_field".to_string(),
-                        ));
-                    }
+                    let v = string_value(name, value)?;
+                    indicator_options.indicator_type = match v.to_uppercase().as_str() {
+                        "EMA" => IndicatorType::EMA,
+                        "SMA" => IndicatorType::SMA,
+                        "ADX" => IndicatorType::ADX,
+                        "AROON" => IndicatorType::AROON,
+                        "SAR" => IndicatorType::SAR,
+                        "SUPERTREND" => IndicatorType::SUPERTREND,
+                        "KELTNER" => IndicatorType::KELTNER,
+                        "DONCHIAN" => IndicatorType::DONCHIAN,
+                        "CCI" => IndicatorType::CCI,
+                        "ROC" => IndicatorType::ROC,
+                        "MOM" => IndicatorType::MOM,
+                        "TRIX" => IndicatorType::TRIX,
+                        "ULTOSC" => IndicatorType::ULTOSC,
+                        "ICHIMOKU" => IndicatorType::ICHIMOKU,
+                        "STDDEV" => IndicatorType::STDDEV,
+                        "VARIANCE" => IndicatorType::VARIANCE,
+                        "ZSCORE" => IndicatorType::ZSCORE,
+                        "PERCENTRANK" => IndicatorType::PERCENTRANK,
+                        "MIN" => IndicatorType::MIN,
+                        "MAX" => IndicatorType::MAX,
+                        "MEDIAN" => IndicatorType::MEDIAN,
+                        "RETURN" => IndicatorType::RETURN,
+                        "LOGRETURN" => IndicatorType::LOGRETURN,
+                        "REALIZEDVOL" => IndicatorType::REALIZEDVOL,
+                        "PARKINSON" => IndicatorType::PARKINSON,
+                        "GARMANKLASS" => IndicatorType::GARMANKLASS,
+                        "DRAWDOWN" => IndicatorType::DRAWDOWN,
+                        "LINREG" => IndicatorType::LINREG,
+                        _ => return Err(IndicatorOptionError::InvalidIndicatorType(v)),
+                    };
+                }
+                "period" => indicator_options.period = int_value(name, value)? as u32,
+                "field" => indicator_options.field = string_value(name, value)?,
+                "as" => indicator_options.as_field = string_value(name, value)?,
+                "ticker_field" => indicator_options.ticker_field = string_value(name, value)?,
+                "high_field" => indicator_options.high_field = string_value(name, value)?,
+                "low_field" => indicator_options.low_field = string_value(name, value)?,
+                "close_field" => indicator_options.close_field = string_value(name, value)?,
+                "open_field" => indicator_options.open_field = string_value(name, value)?,
+                "annualization" => {
+                    indicator_options.annualization = non_negative_double(name, value)?;
+                }
+                "multiplier" => {
+                    indicator_options.multiplier = non_negative_double(name, value)?;
+                }
+                "atr_period" => {
+                    indicator_options.atr_period =
+                        non_negative(name, int_value(name, value)?)? as u32
+                }
+                "medium_period" => {
+                    indicator_options.medium_period =
+                        non_negative(name, int_value(name, value)?)? as u32
+                }
+                "long_period" => {
+                    indicator_options.long_period =
+                        non_negative(name, int_value(name, value)?)? as u32
+                }
+                "displacement" => {
+                    indicator_options.displacement =
+                        non_negative(name, int_value(name, value)?)? as u32
+                }
+                "acceleration" => {
+                    indicator_options.acceleration = non_negative_double(name, value)?;
+                }
+                "max_acceleration" => {
+                    indicator_options.max_acceleration = non_negative_double(name, value)?;
+                }
+                "regression_x" => {
+                    let v = string_value(name, value)?;
+                    indicator_options.regression_x = match v.to_lowercase().as_str() {
+                        "index" => RegressionX::Index,
+                        "time" => RegressionX::Time,
+                        _ => {
+                            return Err(IndicatorOptionError::InvalidOptionValue(
+                                name.to_string(),
+                                v,
+                            ))
+                        }
+                    };
+                }
+                "forecast" => {
+                    indicator_options.forecast = non_negative(name, int_value(name, value)?)? as u32
+                }
+                "signal_on" => indicator_options.signal_on = string_value(name, value)?,
+                "signal_cross" => indicator_options.signal_cross = string_value(name, value)?,
+                "signal_threshold" => {
+                    indicator_options.signal_threshold = double_value(name, value)?;
+                }
+                "signal_tag" => indicator_options.signal_tag = bool_value(name, value)?,
+                "snapshot_format" => {
+                    let v = string_value(name, value)?;
+                    indicator_options.snapshot_format = match v.to_lowercase().as_str() {
+                        "json" => SnapshotFormat::Json,
+                        "binary" => SnapshotFormat::Binary,
+                        _ => {
+                            return Err(IndicatorOptionError::InvalidOptionValue(
+                                name.to_string(),
+                                v,
+                            ))
+                        }
+                    };
+                }
+                "max_series" => {
+                    indicator_options.max_series =
+                        non_negative(name, int_value(name, value)?)? as usize
+                }
+                "series_ttl" => {
+                    indicator_options.series_ttl = non_negative(name, duration_value(name, value)?)?
+                }
+                "max_gap" => {
+                    indicator_options.max_gap = non_negative(name, duration_value(name, value)?)?
+                }
+                "gap_policy" => {
+                    let v = string_value(name, value)?;
+                    indicator_options.gap_policy = match v.to_lowercase().as_str() {
+                        "reset" => GapPolicy::Reset,
+                        "carry" => GapPolicy::Carry,
+                        "fill" => GapPolicy::Fill,
+                        _ => {
+                            return Err(IndicatorOptionError::InvalidOptionValue(
+                                name.to_string(),
+                                v,
+                            ))
+                        }
+                    };
+                }
+                "late_policy" => {
+                    let v = string_value(name, value)?;
+                    indicator_options.late_policy = match v.to_lowercase().as_str() {
+                        "drop" => LatePolicy::Drop,
+                        "recompute" => LatePolicy::Recompute,
+                        "warn" => LatePolicy::Warn,
+                        _ => {
+                            return Err(IndicatorOptionError::InvalidOptionValue(
+                                name.to_string(),
+                                v,
+                            ))
+                        }
+                    };
+                }
+                "replay_window" => {
+                    indicator_options.replay_window =
+                        non_negative(name, int_value(name, value)?)? as usize
+                }
+                "suppress_empty" => indicator_options.suppress_empty = bool_value(name, value)?,
+                "max_batch_points" => {
+                    indicator_options.max_batch_points =
+                        non_negative(name, int_value(name, value)?)? as usize
+                }
+                "overflow_policy" => {
+                    let v = string_value(name, value)?;
+                    indicator_options.overflow_policy = match v.to_lowercase().as_str() {
+                        "error" => OverflowPolicy::Error,
+                        "stream" => OverflowPolicy::Stream,
+                        "truncate" => OverflowPolicy::Truncate,
+                        _ => {
+                            return Err(IndicatorOptionError::InvalidOptionValue(
+                                name.to_string(),
+                                v,
+                            ))
+                        }
+                    };
+                }
+                "on_reject" => {
+                    let v = string_value(name, value)?;
+                    indicator_options.on_reject = match v.to_lowercase().as_str() {
+                        "drop" => RejectPolicy::Drop,
+                        "pass" => RejectPolicy::Pass,
+                        "summary" => RejectPolicy::Summary,
+                        _ => {
+                            return Err(IndicatorOptionError::InvalidOptionValue(
+                                name.to_string(),
+                                v,
+                            ))
+                        }
+                    };
                 }
                 _ => {
                     return Err(IndicatorOptionError::UnknownOption(option.name.clone()));
@@ -107,95 +470,296 @@ impl IndicatorOptions {
             }
         }
 
+        for (name, period) in [
+            ("period", indicator_options.period),
+            ("atr_period", indicator_options.atr_period),
+        ] {
+            if period == 0 {
+                return Err(IndicatorOptionError::InvalidOptionValue(
+                    name.to_string(),
+                    "0".to_string(),
+                ));
+            }
+        }
+        if indicator_options.acceleration > indicator_options.max_acceleration {
+            return Err(IndicatorOptionError::InvalidOptionValue(
+                "acceleration".to_string(),
+                format!(
+                    "{} exceeds max_acceleration {}",
+                    indicator_options.acceleration, indicator_options.max_acceleration
+                ),
+            ));
+        }
+
         Ok(indicator_options)
     }
 
     pub fn to_option_info(&self) -> HashMap<String, OptionInfo> {
-        let mut options = HashMap::new();
-
-        options.insert(
-            "type".to_string(),
-            OptionInfo {
-                value_types: vec![ValueType::String as i32],
-            },
-        );
-        options.insert(
-            "period".to_string(),
-            OptionInfo {
-                value_types: vec![ValueType::Int as i32],
-            },
-        );
-        options.insert(
-            "field".to_string(),
-            OptionInfo {
-                value_types: vec![ValueType::String as i32],
-            },
-        );
-        options.insert(
-            "as".to_string(),
-            OptionInfo {
-                value_types: vec![ValueType::String as i32],
-            },
-        );
-        options.insert(
-            "ticker_field".to_string(),
-            OptionInfo {
-                value_types: vec![ValueType::String as i32],
-            },
-        );
-
-        options
+        OPTION_TYPES
+            .iter()
+            .map(|(name, value_type)| {
+                (
+                    name.to_string(),
+                    OptionInfo {
+                        value_types: vec![*value_type as i32],
+                    },
+                )
+            })
+            .collect()
     }
 
     pub fn to_proto_options(&self) -> Vec<ProtoOption> {
         vec![
-            ProtoOption {
-                name: "type".to_string(),
-                values: vec![OptionValue {
-                    r#type: ValueType::String as i32,
-                    value: Some(kapacitor_udf::proto::option_value::Value::StringValue(
-                        format!("{:?}", self.indicator_type),
-                    )),
-                }],
-            },
-            ProtoOption {
-                name: "period".to_string(),
-                values: vec![OptionValue {
-                    r#type: ValueType::Int as i32,
-                    value: Some(kapacitor_udf::proto::option_value::Value::IntValue(
-                        self.period as i64,
-                    )),
-                }],
-            },
-            ProtoOption {
-                name: "field".to_string(),
-                values: vec![OptionValue {
-                    r#type: ValueType::String as i32,
-                    value: Some(kapacitor_udf::proto::option_value::Value::StringValue(
-                        self.field.clone(),
-                    )),
-                }],
-            },
-            ProtoOption {
-                name: "as".to_string(),
-                values: vec![OptionValue {
-                    r#type: ValueType::String as i32,
-                    value: Some(kapacitor_udf::proto::option_value::Value::StringValue(
-                        self.as_field.clone(),
-                    )),
-                }],
-            },
-            ProtoOption {
-                name: "ticker_field".to_string(),
-                values: vec![OptionValue {
-                    r#type: ValueType::String as i32,
-                    value: Some(kapacitor_udf::proto::option_value::Value::StringValue(
-                        self.ticker_field.clone(),
-                    )),
-                }],
-            },
+            proto_option(
+                "type",
+                ValueType::String,
+                Value::StringValue(format!("{:?}", self.indicator_type)),
+            ),
+            proto_option(
+                "period",
+                ValueType::Int,
+                Value::IntValue(self.period as i64),
+            ),
+            proto_option(
+                "field",
+                ValueType::String,
+                Value::StringValue(self.field.clone()),
+            ),
+            proto_option(
+                "as",
+                ValueType::String,
+                Value::StringValue(self.as_field.clone()),
+            ),
+            proto_option(
+                "ticker_field",
+                ValueType::String,
+                Value::StringValue(self.ticker_field.clone()),
+            ),
+            proto_option(
+                "high_field",
+                ValueType::String,
+                Value::StringValue(self.high_field.clone()),
+            ),
+            proto_option(
+                "low_field",
+                ValueType::String,
+                Value::StringValue(self.low_field.clone()),
+            ),
+            proto_option(
+                "close_field",
+                ValueType::String,
+                Value::StringValue(self.close_field.clone()),
+            ),
+            proto_option(
+                "open_field",
+                ValueType::String,
+                Value::StringValue(self.open_field.clone()),
+            ),
+            proto_option(
+                "annualization",
+                ValueType::Double,
+                Value::DoubleValue(self.annualization),
+            ),
+            proto_option(
+                "multiplier",
+                ValueType::Double,
+                Value::DoubleValue(self.multiplier),
+            ),
+            proto_option(
+                "atr_period",
+                ValueType::Int,
+                Value::IntValue(self.atr_period as i64),
+            ),
+            proto_option(
+                "medium_period",
+                ValueType::Int,
+                Value::IntValue(self.medium_period as i64),
+            ),
+            proto_option(
+                "long_period",
+                ValueType::Int,
+                Value::IntValue(self.long_period as i64),
+            ),
+            proto_option(
+                "displacement",
+                ValueType::Int,
+                Value::IntValue(self.displacement as i64),
+            ),
+            proto_option(
+                "acceleration",
+                ValueType::Double,
+                Value::DoubleValue(self.acceleration),
+            ),
+            proto_option(
+                "max_acceleration",
+                ValueType::Double,
+                Value::DoubleValue(self.max_acceleration),
+            ),
+            proto_option(
+                "regression_x",
+                ValueType::String,
+                Value::StringValue(format!("{:?}", self.regression_x).to_lowercase()),
+            ),
+            proto_option(
+                "forecast",
+                ValueType::Int,
+                Value::IntValue(self.forecast as i64),
+            ),
+            proto_option(
+                "signal_on",
+                ValueType::String,
+                Value::StringValue(self.signal_on.clone()),
+            ),
+            proto_option(
+                "signal_cross",
+                ValueType::String,
+                Value::StringValue(self.signal_cross.clone()),
+            ),
+            proto_option(
+                "signal_threshold",
+                ValueType::Double,
+                Value::DoubleValue(self.signal_threshold),
+            ),
+            proto_option(
+                "signal_tag",
+                ValueType::Bool,
+                Value::BoolValue(self.signal_tag),
+            ),
+            proto_option(
+                "snapshot_format",
+                ValueType::String,
+                Value::StringValue(format!("{:?}", self.snapshot_format).to_lowercase()),
+            ),
+            proto_option(
+                "max_series",
+                ValueType::Int,
+                Value::IntValue(self.max_series as i64),
+            ),
+            proto_option(
+                "series_ttl",
+                ValueType::Duration,
+                Value::DurationValue(self.series_ttl),
+            ),
+            proto_option(
+                "max_gap",
+                ValueType::Duration,
+                Value::DurationValue(self.max_gap),
+            ),
+            proto_option(
+                "gap_policy",
+                ValueType::String,
+                Value::StringValue(format!("{:?}", self.gap_policy).to_lowercase()),
+            ),
+            proto_option(
+                "late_policy",
+                ValueType::String,
+                Value::StringValue(format!("{:?}", self.late_policy).to_lowercase()),
+            ),
+            proto_option(
+                "replay_window",
+                ValueType::Int,
+                Value::IntValue(self.replay_window as i64),
+            ),
+            proto_option(
+                "suppress_empty",
+                ValueType::Bool,
+                Value::BoolValue(self.suppress_empty),
+            ),
+            proto_option(
+                "max_batch_points",
+                ValueType::Int,
+                Value::IntValue(self.max_batch_points as i64),
+            ),
+            proto_option(
+                "overflow_policy",
+                ValueType::String,
+                Value::StringValue(format!("{:?}", self.overflow_policy).to_lowercase()),
+            ),
+            proto_option(
+                "on_reject",
+                ValueType::String,
+                Value::StringValue(format!("{:?}", self.on_reject).to_lowercase()),
+            ),
         ]
     }
+
+    /// Returns the short, medium and long period of `ULTOSC` and `ICHIMOKU` in ascending order.
+    pub fn periods(&self) -> [u32; 3] {
+        let or = |period: u32, default: u32| if period > 0 { period } else { default };
+        let mut periods = [
+            self.period,
+            or(self.medium_period, self.period.saturating_mul(2)),
+            or(self.long_period, self.period.saturating_mul(4)),
+        ];
+        periods.sort_unstable();
+        periods
+    }
+
+    /// Returns the number of bars the `ICHIMOKU` spans are shifted by.
+    pub fn displacement(&self) -> u32 {
+        if self.displacement > 0 {
+            self.displacement
+        } else {
+            self.periods()[1]
+        }
+    }
+
+    /// Returns a stable hash of the options that determine the meaning of the per-ticker state.
+    ///
+    /// Snapshots record this hash so that state computed with one configuration is
+    /// never restored into a task running with another. Options that only affect
+    /// presentation, such as `as` or `snapshot_format`, are deliberately excluded.
+    pub fn state_hash(&self) -> u64 {
+        let mut canonical = format!(
+            "type={:?};period={};field={};ticker_field={}",
+            self.indicator_type, self.period, self.field, self.ticker_field
+        );
+        // Appended only where relevant, so the hash of existing configurations is unchanged
+        if self.indicator_type.uses_ohlc() {
+            canonical += &format!(
+                ";high_field={};low_field={};close_field={}",
+                self.high_field, self.low_field, self.close_field
+            );
+        }
+        if self.indicator_type.uses_open() {
+            canonical += &format!(";open_field={}", self.open_field);
+        }
+        match self.indicator_type {
+            IndicatorType::SAR => {
+                canonical += &format!(
+                    ";acceleration={};max_acceleration={}",
+                    self.acceleration, self.max_acceleration
+                )
+            }
+            IndicatorType::SUPERTREND => canonical += &format!(";multiplier={}", self.multiplier),
+            IndicatorType::KELTNER => canonical += &format!(";atr_period={}", self.atr_period),
+            IndicatorType::ULTOSC | IndicatorType::ICHIMOKU => {
+                canonical += &format!(";periods={:?}", self.periods())
+            }
+            _ => {}
+        }
+
+        stable_hash(&canonical)
+    }
+
+    /// Returns a stable hash of the options that determine the meaning of the
+    /// per-ticker `signal_difference`.
+    ///
+    /// They are kept out of `state_hash`, so that changing the signal only
+    /// discards the side of the last comparison, not the indicator state.
+    pub fn signal_hash(&self) -> u64 {
+        stable_hash(&format!(
+            "signal_on={};signal_cross={};signal_threshold={}",
+            self.signal_on, self.signal_cross, self.signal_threshold
+        ))
+    }
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable across Rust releases.
+fn stable_hash(canonical: &str) -> u64 {
+    canonical.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
 }
 
 impl Default for IndicatorOptions {
@@ -206,6 +770,132 @@ impl Default for IndicatorOptions {
             field: "value".to_string(),
             as_field: "indicator".to_string(),
             ticker_field: "ticker".to_string(),
+            high_field: "high".to_string(),
+            low_field: "low".to_string(),
+            close_field: "close".to_string(),
+            open_field: "open".to_string(),
+            annualization: 252.0,
+            multiplier: 3.0,
+            atr_period: 10,
+            medium_period: 0,
+            long_period: 0,
+            displacement: 0,
+            acceleration: 0.02,
+            max_acceleration: 0.2,
+            regression_x: RegressionX::Index,
+            forecast: 1,
+            signal_on: String::new(),
+            signal_cross: String::new(),
+            signal_threshold: 0.0,
+            signal_tag: false,
+            snapshot_format: SnapshotFormat::Json,
+            max_series: 0,
+            series_ttl: 0,
+            max_gap: 0,
+            gap_policy: GapPolicy::Reset,
+            late_policy: LatePolicy::Drop,
+            replay_window: 1000,
+            suppress_empty: false,
+            max_batch_points: 0,
+            overflow_policy: OverflowPolicy::Error,
+            on_reject: RejectPolicy::Drop,
         }
     }
 }
+
+/// Parses an option given as text, e.g. on the command line, into the option Kapacitor would send.
+///
+/// The value is converted to the type the option is declared with. Durations
+/// are accepted in nanoseconds or with a unit, e.g. `90s` or `1h 30m`.
+pub fn parse_option(name: &str, value: &str) -> Result<ProtoOption, IndicatorOptionError> {
+    let (_, value_type) = OPTION_TYPES
+        .iter()
+        .find(|(option, _)| *option == name)
+        .ok_or_else(|| IndicatorOptionError::UnknownOption(name.to_string()))?;
+    let invalid = |e: &dyn std::fmt::Display| {
+        IndicatorOptionError::InvalidOptionValue(name.to_string(), format!("{}: {}", value, e))
+    };
+
+    let parsed = match value_type {
+        ValueType::Bool => Value::BoolValue(value.parse().map_err(|e| invalid(&e))?),
+        ValueType::Int => Value::IntValue(value.parse().map_err(|e| invalid(&e))?),
+        ValueType::Double => Value::DoubleValue(value.parse().map_err(|e| invalid(&e))?),
+        ValueType::String => Value::StringValue(value.to_string()),
+        ValueType::Duration => match value.parse::<i64>() {
+            Ok(nanos) => Value::DurationValue(nanos),
+            Err(_) => {
+                let duration = humantime::parse_duration(value).map_err(|e| invalid(&e))?;
+                let nanos = i64::try_from(duration.as_nanos()).map_err(|e| invalid(&e))?;
+                Value::DurationValue(nanos)
+            }
+        },
+    };
+
+    Ok(proto_option(name, *value_type, parsed))
+}
+
+fn string_value(name: &str, value: &OptionValue) -> Result<String, IndicatorOptionError> {
+    match value.value {
+        Some(Value::StringValue(ref v)) => Ok(v.clone()),
+        _ => Err(IndicatorOptionError::InvalidOptionType(name.to_string())),
+    }
+}
+
+fn int_value(name: &str, value: &OptionValue) -> Result<i64, IndicatorOptionError> {
+    match value.value {
+        Some(Value::IntValue(v)) => Ok(v),
+        _ => Err(IndicatorOptionError::InvalidOptionType(name.to_string())),
+    }
+}
+
+fn bool_value(name: &str, value: &OptionValue) -> Result<bool, IndicatorOptionError> {
+    match value.value {
+        Some(Value::BoolValue(v)) => Ok(v),
+        _ => Err(IndicatorOptionError::InvalidOptionType(name.to_string())),
+    }
+}
+
+fn double_value(name: &str, value: &OptionValue) -> Result<f64, IndicatorOptionError> {
+    match value.value {
+        Some(Value::DoubleValue(v)) => Ok(v),
+        _ => Err(IndicatorOptionError::InvalidOptionType(name.to_string())),
+    }
+}
+
+fn duration_value(name: &str, value: &OptionValue) -> Result<i64, IndicatorOptionError> {
+    match value.value {
+        Some(Value::DurationValue(v)) => Ok(v),
+        _ => Err(IndicatorOptionError::InvalidOptionType(name.to_string())),
+    }
+}
+
+fn non_negative(name: &str, v: i64) -> Result<i64, IndicatorOptionError> {
+    if v < 0 {
+        return Err(IndicatorOptionError::InvalidOptionValue(
+            name.to_string(),
+            v.to_string(),
+        ));
+    }
+    Ok(v)
+}
+
+fn non_negative_double(name: &str, value: &OptionValue) -> Result<f64, IndicatorOptionError> {
+    let v = double_value(name, value)?;
+    if !(v.is_finite() && v >= 0.0) {
+        return Err(IndicatorOptionError::InvalidOptionValue(
+            name.to_string(),
+            v.to_string(),
+        ));
+    }
+    Ok(v)
+}
+
+fn proto_option(name: &str, value_type: ValueType, value: Value) -> ProtoOption {
+    ProtoOption {
+        name: name.to_string(),
+        values: vec![OptionValue {
+            r#type: value_type as i32,
+            value: Some(value),
+        }],
+    }
+}
@@ -1,8 +1,13 @@
 use super::{
-    config::{IndicatorOptions, IndicatorState, IndicatorType},
-    indicators::Indicator,
+    config::{
+        GapPolicy, IndicatorOptions, IndicatorState, LatePolicy, OverflowPolicy, RejectPolicy,
+    },
+    indicators::{self, Bar, Indicator, Output},
+    metrics::{DropReason, Latency, Metrics, TaskMetrics},
+    point_trace::{self, PointTrace},
+    signal, snapshot,
+    state_store::StateStore,
 };
-use crate::handler::indicators::{ema::Ema, sma::Sma};
 use async_std::{channel::Sender, sync::Mutex};
 use async_trait::async_trait;
 use kapacitor_udf::{
@@ -12,10 +17,18 @@ use kapacitor_udf::{
     },
     traits::Handler,
 };
-use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, io, sync::Arc};
+use prost::Message;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    io,
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
 use thiserror::Error;
-use tracing::{debug, error, instrument, trace, warn};
+use tracing::{debug, error, info, instrument, trace, warn};
+
+/// Upper bound on the number of intervals forward-filled for a single gap.
+const MAX_FILL_POINTS: i64 = 100_000;
 
 #[derive(Debug, Error)]
 pub enum IndicatorError {
@@ -25,13 +38,43 @@ pub enum IndicatorError {
     InvalidFieldType(String),
     #[error("Missing ticker field: {0}")]
     MissingTickerField(String),
+    #[error("State store error: {0}")]
+    StateStoreError(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
 struct IndicatorData {
     states: HashMap<String, IndicatorState>,
-    #[serde(skip)]
     batch_points: Vec<Point>,
+    /// Approximate size of `batch_points`, accounted in `Metrics::buffered_bytes`.
+    batch_bytes: usize,
+    /// Points dropped from the current batch because it exceeded `max_batch_points`.
+    truncated: usize,
+    /// Points of the current batch rejected for a missing ticker or field, for `RejectPolicy::Summary`.
+    rejected: BTreeMap<DropReason, u64>,
+    /// Tickers whose state changed since the last flush to the state store.
+    dirty: HashSet<String>,
+    /// Recently processed inputs per ticker, used to detect re-delivered late
+    /// points and to replay points for `LatePolicy::Recompute`.
+    journals: HashMap<String, Journal>,
+}
+
+/// The last `replay_window` inputs of a ticker.
+#[derive(Debug, Clone, Default)]
+struct Journal {
+    /// The ticker's state before the first entry, only kept for `LatePolicy::Recompute`.
+    checkpoint: Option<IndicatorState>,
+    /// The inputs, kept under `LatePolicy::Recompute` and `LatePolicy::Warn`.
+    entries: VecDeque<Bar>,
+    /// The timestamps of the inputs in ascending order, kept instead of the
+    /// inputs under `LatePolicy::Drop`, which processes nothing out of order.
+    times: VecDeque<i64>,
+}
+
+impl Journal {
+    fn contains(&self, time: i64) -> bool {
+        self.times.binary_search(&time).is_ok() || self.entries.iter().any(|b| b.time == time)
+    }
 }
 
 pub struct IndicatorHandler {
@@ -40,6 +83,16 @@ pub struct IndicatorHandler {
     data: IndicatorData,
     indicator: Box<dyn Indicator + Send>,
     begin_batch: Option<BeginBatch>,
+    state_store: Option<Arc<dyn StateStore>>,
+    task_id: String,
+    /// Whether `task_id` was set by a successful init, which registers it with `metrics`.
+    task_started: bool,
+    /// Metrics to report to, if enabled through `with_metrics`.
+    metrics: Option<Arc<Metrics>>,
+    /// The counters of `task_id` and the compute latency of the indicator,
+    /// looked up in `metrics` on init so that points are counted without locking.
+    task_metrics: Option<(Arc<TaskMetrics>, Arc<Latency>)>,
+    point_trace: Option<Arc<PointTrace>>,
 }
 
 impl IndicatorHandler {
@@ -47,10 +100,7 @@ impl IndicatorHandler {
     pub async fn new(responses: Arc<Mutex<Sender<Response>>>, options: IndicatorOptions) -> Self {
         debug!("Creating new IndicatorHandler");
 
-        let indicator: Box<dyn Indicator + Send> = match options.indicator_type {
-            IndicatorType::EMA => Box::new(Ema),
-            IndicatorType::SMA => Box::new(Sma),
-        };
+        let indicator = indicators::for_type(&options.indicator_type);
 
         IndicatorHandler {
             responses,
@@ -58,22 +108,502 @@ impl IndicatorHandler {
             data: IndicatorData {
                 states: HashMap::new(),
                 batch_points: Vec::new(),
+                batch_bytes: 0,
+                truncated: 0,
+                rejected: BTreeMap::new(),
+                dirty: HashSet::new(),
+                journals: HashMap::new(),
             },
             indicator,
             begin_batch: None,
+            state_store: None,
+            task_id: String::new(),
+            task_started: false,
+            metrics: None,
+            task_metrics: None,
+            point_trace: None,
         }
     }
 
-    #[instrument(skip(self))]
+    /// Reports runtime metrics to the given shared `Metrics`.
+    ///
+    /// Without metrics, the handler records none.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.take_batch_points();
+        self.end_task();
+        if let Some(previous) = &self.metrics {
+            previous.active_agents.fetch_sub(1, Ordering::Relaxed);
+        }
+        metrics.active_agents.fetch_add(1, Ordering::Relaxed);
+        self.metrics = Some(metrics);
+        self.start_task();
+        self
+    }
+
+    /// Registers `task_id` with `metrics` once it is set by init.
+    fn start_task(&mut self) {
+        if let (Some(metrics), true) = (&self.metrics, self.task_started) {
+            self.task_metrics = Some((
+                metrics.task_started(&self.task_id),
+                metrics.latency(&format!("{:?}", self.options.indicator_type)),
+            ));
+        }
+    }
+
+    /// Unregisters `task_id` from `metrics`.
+    fn end_task(&mut self) {
+        if let (Some(metrics), Some(_)) = (&self.metrics, self.task_metrics.take()) {
+            metrics.task_ended(&self.task_id);
+        }
+    }
+
+    /// Updates the counters of the task, if metrics are enabled.
+    fn count(&self, f: impl FnOnce(&TaskMetrics)) {
+        if let Some((task, _)) = &self.task_metrics {
+            f(task);
+        }
+    }
+
+    /// Persists per-ticker state to the given store in addition to Kapacitor snapshots.
+    ///
+    /// States are loaded from the store in `init` and flushed after every `end_batch`.
+    pub fn with_state_store(mut self, state_store: Arc<dyn StateStore>) -> Self {
+        self.state_store = Some(state_store);
+        self
+    }
+
+    /// Logs the processing of the points selected by the given `PointTrace` at info level.
+    pub fn with_point_trace(mut self, point_trace: Arc<PointTrace>) -> Self {
+        self.point_trace = Some(point_trace);
+        self
+    }
+
+    /// Buffers a point of the current batch and accounts for its memory.
+    fn buffer_point(&mut self, p: &Point) {
+        let bytes = p.encoded_len();
+        self.data.batch_points.push(p.clone());
+        self.data.batch_bytes += bytes;
+        if let Some(metrics) = &self.metrics {
+            metrics.buffered_points.fetch_add(1, Ordering::Relaxed);
+            metrics
+                .buffered_bytes
+                .fetch_add(bytes as i64, Ordering::Relaxed);
+        }
+    }
+
+    /// Takes all buffered points of the current batch and releases their memory accounting.
+    fn take_batch_points(&mut self) -> Vec<Point> {
+        let points = std::mem::take(&mut self.data.batch_points);
+        let bytes = std::mem::take(&mut self.data.batch_bytes);
+        if let Some(metrics) = &self.metrics {
+            metrics
+                .buffered_points
+                .fetch_sub(points.len() as i64, Ordering::Relaxed);
+            metrics
+                .buffered_bytes
+                .fetch_sub(bytes as i64, Ordering::Relaxed);
+        }
+        points
+    }
+
+    /// Processes the buffered points and forwards them as a batch of their own.
+    ///
+    /// Used by `OverflowPolicy::Stream` once a batch outgrows `max_batch_points`.
+    /// The chunk is framed like the batch it belongs to, with the size of its
+    /// output and the time of its newest point, so every BeginBatch announces
+    /// exactly the points that follow it.
+    async fn stream_buffered(&mut self) -> Result<(), IndicatorError> {
+        let Some(begin) = self.begin_batch.clone() else {
+            return Ok(());
+        };
+
+        let points = self.take_batch_points();
+        let tmax = points.iter().map(|p| p.time).max().unwrap_or_default();
+        let output = self.process_points(points).await?;
+        if output.is_empty() && self.options.suppress_empty {
+            debug!("Suppressing empty chunk of streamed batch");
+            return Ok(());
+        }
+
+        debug!("Streaming chunk of {} points", output.len());
+        let end = EndBatch {
+            name: begin.name.clone(),
+            group: begin.group.clone(),
+            tmax,
+            tags: begin.tags.clone(),
+            by_name: begin.by_name,
+        };
+        self.send_batch(begin, output, &end).await
+    }
+
+    /// Calculates the indicator for a set of points and returns the points to emit.
+    ///
+    /// Points are processed in time order, keeping only the last of several
+    /// points delivered for the same ticker and timestamp. Points rejected
+    /// under `RejectPolicy::Pass` are passed through unchanged. Outputs plotted
+    /// ahead of or behind their bar are placed by `place_shifted`.
+    async fn process_points(
+        &mut self,
+        mut points: Vec<Point>,
+    ) -> Result<Vec<Point>, IndicatorError> {
+        points.sort_by_key(|p| p.time);
+        let mut seen = HashSet::new();
+        let mut points: Vec<Point> = points
+            .into_iter()
+            .rev()
+            .filter(|p| {
+                match (
+                    p.tags.get(&self.options.ticker_field),
+                    Bar::from_point(p, &self.options),
+                ) {
+                    (Some(ticker), Some(_)) => {
+                        let first = seen.insert((ticker.clone(), p.time));
+                        if !first {
+                            self.count(|m| m.point_dropped(DropReason::Duplicate));
+                        }
+                        first
+                    }
+                    _ => true,
+                }
+            })
+            .collect();
+        points.reverse();
+
+        let mut output = Vec::with_capacity(points.len());
+        let mut shifted = Vec::new();
+        let mut emitted = 0;
+        for mut point in points {
+            let (Some(ticker), Some(bar)) = (
+                point.tags.get(&self.options.ticker_field).cloned(),
+                Bar::from_point(&point, &self.options),
+            ) else {
+                // Only points rejected under RejectPolicy::Pass are buffered without them
+                output.push(point);
+                continue;
+            };
+
+            let Some(outputs) = self.calculate_indicator(&ticker, bar).await? else {
+                continue;
+            };
+
+            // Points seen during an indicator's warm-up are forwarded without its outputs
+            if !outputs.is_empty() {
+                emitted += 1;
+            }
+            for o in outputs {
+                if o.shift == 0 {
+                    o.write_to(&mut point, &self.options.as_field);
+                } else {
+                    shifted.push((ticker.clone(), point.time, o));
+                }
+            }
+            if !self.options.signal_on.is_empty() {
+                self.mark_signal(&ticker, &mut point);
+            }
+
+            output.push(point);
+        }
+
+        if !shifted.is_empty() {
+            self.place_shifted(&mut output, shifted);
+        }
+
+        self.count(|m| {
+            m.points_emitted.fetch_add(emitted, Ordering::Relaxed);
+        });
+
+        Ok(output)
+    }
+
+    /// Marks a point on which `signal_on` crossed its reference with `cross_up`
+    /// or `cross_down` in the `<as>_signal` field, or tag with `signal_tag`.
+    ///
+    /// Late points are not compared with the points before them, so they
+    /// neither signal nor move the side the ticker was last on.
+    fn mark_signal(&mut self, ticker: &str, point: &mut Point) {
+        let Some(state) = self.data.states.get_mut(ticker) else {
+            return;
+        };
+        if state
+            .last_time
+            .is_some_and(|last_time| point.time < last_time)
+        {
+            return;
+        }
+        let Some(cross) = signal::detect(&self.options, point, &mut state.signal_difference) else {
+            return;
+        };
+
+        let name = format!("{}_signal", self.options.as_field);
+        let label = cross.label().to_string();
+        if self.options.signal_tag {
+            point.tags.insert(name, label);
+        } else {
+            point.fields_string.insert(name, label);
+        }
+    }
+
+    /// Writes outputs plotted ahead of or behind their bar into the points at the shifted time.
+    ///
+    /// The bar interval of a ticker is the smallest spacing of its points in
+    /// the batch, or the spacing of its last two points if the batch holds only
+    /// one. An output whose shifted time matches a point of the same ticker is
+    /// added to that point, otherwise it is emitted in a new point carrying the
+    /// name, group and tags of the point it was calculated for. Outputs of
+    /// tickers without a known interval are dropped.
+    fn place_shifted(&self, output: &mut Vec<Point>, shifted: Vec<(String, i64, Output)>) {
+        let mut intervals: HashMap<&str, i64> = HashMap::new();
+        let mut last_times: HashMap<&str, i64> = HashMap::new();
+        let mut positions: HashMap<(String, i64), usize> = HashMap::new();
+        for (i, point) in output.iter().enumerate() {
+            let Some(ticker) = point.tags.get(&self.options.ticker_field) else {
+                continue;
+            };
+            if let Some(last) = last_times.insert(ticker, point.time) {
+                let spacing = point.time - last;
+                if spacing > 0 {
+                    let interval = intervals.entry(ticker).or_insert(spacing);
+                    *interval = (*interval).min(spacing);
+                }
+            }
+            positions.insert((ticker.clone(), point.time), i);
+        }
+        let intervals: HashMap<String, i64> = intervals
+            .into_iter()
+            .map(|(ticker, interval)| (ticker.to_string(), interval))
+            .collect();
+
+        let mut added = 0;
+        for (ticker, time, o) in shifted {
+            let interval = intervals.get(&ticker).copied().or_else(|| {
+                self.data
+                    .states
+                    .get(&ticker)
+                    .and_then(|s| s.last_interval)
+                    .filter(|&interval| interval > 0)
+            });
+            let Some(interval) = interval else {
+                debug!(
+                    "Dropping shifted output {} of ticker {}, its interval is not known yet",
+                    o.field_name(&self.options.as_field),
+                    ticker
+                );
+                continue;
+            };
+
+            let target = time.saturating_add(o.shift.saturating_mul(interval));
+            let position = match positions.get(&(ticker.clone(), target)) {
+                Some(&position) => position,
+                None => {
+                    let Some(&source) = positions.get(&(ticker.clone(), time)) else {
+                        continue;
+                    };
+                    let template = &output[source];
+                    let point = Point {
+                        time: target,
+                        name: template.name.clone(),
+                        group: template.group.clone(),
+                        tags: template.tags.clone(),
+                        ..Default::default()
+                    };
+                    added += 1;
+                    output.push(point);
+                    positions.insert((ticker, target), output.len() - 1);
+                    output.len() - 1
+                }
+            };
+            o.write_to(&mut output[position], &self.options.as_field);
+        }
+
+        if added > 0 {
+            trace!("Added {} points for shifted outputs", added);
+            output.sort_by_key(|p| p.time);
+        }
+    }
+
+    /// Builds the point tallying the rejections of the current batch for `RejectPolicy::Summary`.
+    ///
+    /// The point carries the name and group tags of the batch, the time of its
+    /// EndBatch and one `<as>_<reason>` integer field per rejection reason.
+    fn rejection_summary(&mut self, begin: &BeginBatch, end: &EndBatch) -> Point {
+        let rejected = std::mem::take(&mut self.data.rejected);
+        let fields_int = [DropReason::MissingTicker, DropReason::MissingField]
+            .into_iter()
+            .map(|reason| {
+                (
+                    format!("{}_{}", self.options.as_field, reason.label()),
+                    rejected.get(&reason).copied().unwrap_or_default() as i64,
+                )
+            })
+            .collect();
+
+        Point {
+            time: end.tmax,
+            name: begin.name.clone(),
+            group: begin.group.clone(),
+            tags: begin.tags.clone(),
+            fields_int,
+            ..Default::default()
+        }
+    }
+
+    /// Loads the stored states of the current task, if a state store is configured.
+    async fn load_states(&mut self) -> Result<(), IndicatorError> {
+        let Some(store) = &self.state_store else {
+            return Ok(());
+        };
+        if self.task_id.is_empty() {
+            warn!("Init request carries no task ID, state will not be persisted");
+            return Ok(());
+        }
+
+        let stored = store
+            .load(&self.task_id)
+            .await
+            .map_err(|e| IndicatorError::StateStoreError(e.to_string()))?;
+
+        // State computed for another indicator type or other options is discarded, as on restore
+        let mut states = HashMap::new();
+        for (ticker, bytes) in stored {
+            match snapshot::decode(&bytes, &self.options).map(|mut decoded| decoded.remove(&ticker))
+            {
+                Ok(Some(state)) => {
+                    states.insert(ticker, state);
+                }
+                Ok(None) => warn!("Discarding stored state of ticker {}: not found", ticker),
+                Err(e) => {
+                    warn!("Discarding stored state of ticker {}: {}", ticker, e);
+                    if let Err(e) = store.remove(&self.task_id, &ticker).await {
+                        error!("Failed to remove stored state for ticker {}: {}", ticker, e);
+                    }
+                }
+            }
+        }
+        debug!(
+            "Loaded {} ticker states for task {}",
+            states.len(),
+            self.task_id
+        );
+        self.data.states = states;
+        Ok(())
+    }
+
+    /// Drops the state of tickers that stopped reporting.
+    ///
+    /// Tickers idle for longer than `series_ttl`, measured against the newest
+    /// `Point::time` seen across all tickers, are evicted first. If more than
+    /// `max_series` tickers remain, the least recently updated ones are evicted
+    /// until the limit is met.
+    async fn evict_states(&mut self) {
+        let mut evicted = Vec::new();
+
+        if self.options.series_ttl > 0 {
+            if let Some(newest) = self.data.states.values().filter_map(|s| s.last_time).max() {
+                let cutoff = newest.saturating_sub(self.options.series_ttl);
+                self.data.states.retain(|ticker, state| {
+                    let keep = state.last_time.is_some_and(|t| t >= cutoff);
+                    if !keep {
+                        evicted.push(ticker.clone());
+                    }
+                    keep
+                });
+                if !evicted.is_empty() {
+                    info!(
+                        "Evicted {} tickers idle for longer than {}ns",
+                        evicted.len(),
+                        self.options.series_ttl
+                    );
+                }
+            }
+        }
+
+        if self.options.max_series > 0 && self.data.states.len() > self.options.max_series {
+            let excess = self.data.states.len() - self.options.max_series;
+            let mut by_age: Vec<(Option<i64>, String)> = self
+                .data
+                .states
+                .iter()
+                .map(|(ticker, state)| (state.last_time, ticker.clone()))
+                .collect();
+            by_age.sort_unstable();
+            for (_, ticker) in by_age.into_iter().take(excess) {
+                self.data.states.remove(&ticker);
+                evicted.push(ticker);
+            }
+            info!(
+                "Evicted {} least recently updated tickers to stay within max_series {}",
+                excess, self.options.max_series
+            );
+        }
+
+        let tracked = self.data.states.len() as u64;
+        let evicted_count = evicted.len() as u64;
+        self.count(|m| {
+            m.evicted_series.fetch_add(evicted_count, Ordering::Relaxed);
+            m.tracked_series.store(tracked, Ordering::Relaxed);
+        });
+
+        for ticker in evicted {
+            debug!("Evicted state for ticker: {}", ticker);
+            self.data.dirty.remove(&ticker);
+            self.data.journals.remove(&ticker);
+            if let (Some(store), false) = (&self.state_store, self.task_id.is_empty()) {
+                if let Err(e) = store.remove(&self.task_id, &ticker).await {
+                    error!("Failed to remove stored state for ticker {}: {}", ticker, e);
+                }
+            }
+        }
+    }
+
+    /// Writes the state of every ticker touched since the last flush to the state store.
+    ///
+    /// Failures are logged rather than returned, as the in-memory state is still
+    /// intact and the next flush will retry the affected tickers.
+    async fn flush_states(&mut self) {
+        let Some(store) = &self.state_store else {
+            self.data.dirty.clear();
+            return;
+        };
+        if self.task_id.is_empty() {
+            self.data.dirty.clear();
+            return;
+        }
+
+        let mut states = Vec::with_capacity(self.data.dirty.len());
+        for ticker in self.data.dirty.drain() {
+            let Some(state) = self.data.states.get(&ticker) else {
+                continue;
+            };
+            match snapshot::encode_ticker(&self.options, &ticker, state) {
+                Ok(bytes) => states.push((ticker, bytes)),
+                Err(e) => error!("Failed to encode state for ticker {}: {}", ticker, e),
+            }
+        }
+        if let Err(e) = store.save_many(&self.task_id, &states).await {
+            error!("Failed to persist state of {} tickers: {}", states.len(), e);
+            self.data.dirty = states.into_iter().map(|(ticker, _)| ticker).collect();
+        }
+    }
+
+    /// Calculates the indicator for a bar of the given ticker.
+    ///
+    /// Returns `None` if the point is dropped because its timestamp was already
+    /// processed for the ticker, or because it arrived late and `late_policy`
+    /// does not allow processing it.
+    #[instrument(level = "trace", skip(self))]
     async fn calculate_indicator(
         &mut self,
         ticker: &str,
-        value: f64,
-    ) -> Result<f64, IndicatorError> {
-        debug!(
+        bar: Bar,
+    ) -> Result<Option<Vec<Output>>, IndicatorError> {
+        trace!(
             "Calculating indicator for ticker: {}, value: {}",
-            ticker, value
+            ticker,
+            bar.value
         );
+        let (time, value) = (bar.time, bar.value);
+
+        let traced = self.point_trace.as_ref().is_some_and(|t| t.select(ticker));
 
         let state = self
             .data
@@ -81,37 +611,220 @@ impl IndicatorHandler {
             .entry(ticker.to_string())
             .or_insert_with(|| {
                 debug!("Initializing new state for ticker: {}", ticker);
-                IndicatorState {
-                    current_value: None,
-                    values: Vec::new(),
-                    count: 0,
+                IndicatorState::default()
+            });
+
+        trace!("State before calculation: {:?}", state);
+
+        let journal = self.data.journals.get(ticker);
+        let already_processed =
+            state.last_time == Some(time) || journal.is_some_and(|j| j.contains(time));
+        if already_processed {
+            trace!(
+                "Dropping duplicate point for ticker {} at time {}",
+                ticker,
+                time
+            );
+            if traced {
+                info!(target: point_trace::TARGET, ticker, time, value, "Dropped duplicate point");
+            }
+            self.count(|m| m.point_dropped(DropReason::Duplicate));
+            return Ok(None);
+        }
+
+        if let Some(last_time) = state.last_time.filter(|&last_time| time < last_time) {
+            match self.options.late_policy {
+                LatePolicy::Drop => {
+                    warn!(
+                        "Dropping late point for ticker {} at time {}, last processed time is {}",
+                        ticker, time, last_time
+                    );
+                    if traced {
+                        info!(target: point_trace::TARGET, ticker, time, value, last_time, "Dropped late point");
+                    }
+                    self.count(|m| m.point_dropped(DropReason::Late));
+                    return Ok(None);
+                }
+                LatePolicy::Warn => {
+                    warn!(
+                        "Processing late point for ticker {} at time {} out of order, last processed time is {}",
+                        ticker, time, last_time
+                    );
                 }
+                LatePolicy::Recompute => {
+                    let result = self.recompute(ticker, bar).await;
+                    if traced {
+                        info!(target: point_trace::TARGET, ticker, time, value, result = ?result, "Recomputed late point");
+                    }
+                    return Ok(result);
+                }
+            }
+        }
+
+        let journal = self
+            .data
+            .journals
+            .entry(ticker.to_string())
+            .or_insert_with(|| Journal {
+                checkpoint: (self.options.late_policy == LatePolicy::Recompute)
+                    .then(|| state.clone()),
+                ..Journal::default()
             });
+        if self.options.late_policy == LatePolicy::Drop {
+            journal.times.push_back(time);
+        } else {
+            journal.entries.push_back(bar);
+        }
 
-        debug!("State before calculation: {:?}", state);
+        let started = self.task_metrics.is_some().then(Instant::now);
+        let result = apply_point(&mut *self.indicator, &self.options, ticker, state, &bar).await;
+        if let (Some((_, latency)), Some(started)) = (&self.task_metrics, started) {
+            latency.record(started.elapsed());
+        }
 
-        let result = self
-            .indicator
-            .calculate(state, self.options.period.try_into().unwrap(), value)
-            .await;
+        trace!(
+            "Calculated result for ticker: {}, input: {}, output: {:?}, indicator type: {:?}",
+            ticker,
+            value,
+            result,
+            self.options.indicator_type
+        );
+        trace!("State after calculation: {:?}", state);
+        if traced {
+            info!(target: point_trace::TARGET, ticker, time, value, result = ?result, state = ?state, "Calculated indicator");
+        }
 
-        debug!(
-            "Calculated result for ticker: {}, input: {}, output: {}, indicator type: {:?}",
-            ticker, value, result, self.options.indicator_type
+        self.trim_journal(ticker).await;
+        self.data.dirty.insert(ticker.to_string());
+
+        Ok(Some(result))
+    }
+
+    /// Processes a late point by rewinding the ticker to its journal checkpoint
+    /// and replaying the journaled points with the late one inserted in order.
+    ///
+    /// Returns the indicator outputs at the late point, or `None` if it is older
+    /// than the journal reaches back.
+    async fn recompute(&mut self, ticker: &str, bar: Bar) -> Option<Vec<Output>> {
+        let time = bar.time;
+        let journal = match self.data.journals.get_mut(ticker) {
+            Some(journal)
+                if journal
+                    .checkpoint
+                    .as_ref()
+                    .is_some_and(|checkpoint| checkpoint.last_time.map_or(true, |t| t < time)) =>
+            {
+                journal
+            }
+            _ => {
+                warn!(
+                    "Dropping late point for ticker {} at time {}, it is older than the replay window",
+                    ticker, time
+                );
+                self.count(|m| m.point_dropped(DropReason::Late));
+                return None;
+            }
+        };
+
+        let position = journal.entries.partition_point(|b| b.time < time);
+        journal.entries.insert(position, bar);
+        info!(
+            "Recomputing ticker {} from time {} over {} journaled points",
+            ticker,
+            time,
+            journal.entries.len() - position
         );
 
-        debug!("State after calculation: {:?}", state);
+        let mut state = journal.checkpoint.clone().unwrap_or_default();
+        // The signal compares consecutive points as they arrive, which replaying does not change
+        state.signal_difference = self
+            .data
+            .states
+            .get(ticker)
+            .and_then(|s| s.signal_difference);
+        let mut result = None;
+        for b in &journal.entries {
+            let r = apply_point(&mut *self.indicator, &self.options, ticker, &mut state, b).await;
+            if b.time == time {
+                result = Some(r);
+            }
+        }
+
+        self.data.states.insert(ticker.to_string(), state);
+        self.trim_journal(ticker).await;
+        self.data.dirty.insert(ticker.to_string());
+
+        result
+    }
 
-        Ok(result)
+    /// Drops journal entries beyond `replay_window`, folding them into the checkpoint if one is kept.
+    async fn trim_journal(&mut self, ticker: &str) {
+        let Some(journal) = self.data.journals.get_mut(ticker) else {
+            return;
+        };
+        while journal.times.len() > self.options.replay_window {
+            journal.times.pop_front();
+        }
+        while journal.entries.len() > self.options.replay_window {
+            let Some(bar) = journal.entries.pop_front() else {
+                break;
+            };
+            if let Some(checkpoint) = &mut journal.checkpoint {
+                apply_point(
+                    &mut *self.indicator,
+                    &self.options,
+                    ticker,
+                    checkpoint,
+                    &bar,
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Sends a complete batch to Kapacitor, framed by a single BeginBatch and EndBatch.
+    ///
+    /// The size of the BeginBatch is set to the number of points actually sent,
+    /// which differs from the input size when points were filtered out.
+    async fn send_batch(
+        &self,
+        begin: BeginBatch,
+        points: Vec<Point>,
+        end: &EndBatch,
+    ) -> Result<(), IndicatorError> {
+        let begin = BeginBatch {
+            size: points.len() as i64,
+            ..begin
+        };
+
+        debug!("Sending BeginBatch with size {}", begin.size);
+        self.send_response(Response {
+            message: Some(response::Message::Begin(begin)),
+        })
+        .await?;
+
+        for point in points {
+            trace!("Sending point: {:?}", point);
+            self.send_response(Response {
+                message: Some(response::Message::Point(point)),
+            })
+            .await?;
+        }
+
+        debug!("Sending EndBatch");
+        self.send_response(Response {
+            message: Some(response::Message::End(end.clone())),
+        })
+        .await
     }
 
     async fn send_response(&self, response: Response) -> Result<(), IndicatorError> {
-        debug!("Sending response: {:?}", response);
+        trace!("Sending response: {:?}", response);
 
         let sender = self.responses.lock().await;
         match sender.send(response).await {
             Ok(_) => {
-                debug!("Response sent successfully");
+                trace!("Response sent successfully");
                 Ok(())
             }
             Err(e) => {
@@ -122,6 +835,76 @@ impl IndicatorHandler {
     }
 }
 
+/// Applies a single bar to a ticker's state, handling gaps according to the options.
+async fn apply_point(
+    indicator: &mut (dyn Indicator + Send),
+    options: &IndicatorOptions,
+    ticker: &str,
+    state: &mut IndicatorState,
+    bar: &Bar,
+) -> Vec<Output> {
+    let time = bar.time;
+    // A late point processed under LatePolicy::Warn is no gap and does not move the ticker back in time
+    let late = state.last_time.is_some_and(|last_time| time < last_time);
+    let gap = state
+        .last_time
+        .filter(|_| !late)
+        .map(|last_time| time - last_time);
+
+    if let Some(gap) = gap.filter(|&gap| options.max_gap > 0 && gap > options.max_gap) {
+        match options.gap_policy {
+            GapPolicy::Reset => {
+                info!(
+                    "Gap of {}ns for ticker {} exceeds max_gap, resetting state",
+                    gap, ticker
+                );
+                *state = IndicatorState::default();
+            }
+            GapPolicy::Carry => {
+                debug!(
+                    "Gap of {}ns for ticker {} exceeds max_gap, carrying on",
+                    gap, ticker
+                );
+            }
+            GapPolicy::Fill => match (state.last_value, state.last_interval) {
+                (Some(last_value), Some(interval)) if interval > 0 => {
+                    let missing = (gap - 1) / interval;
+                    if missing > MAX_FILL_POINTS {
+                        warn!(
+                            "Gap for ticker {} spans {} intervals, too many to fill, resetting state",
+                            ticker, missing
+                        );
+                        *state = IndicatorState::default();
+                    } else {
+                        debug!(
+                            "Forward-filling {} intervals for ticker {} with {}",
+                            missing, ticker, last_value
+                        );
+                        let last_time = state.last_time.unwrap_or(time);
+                        for i in 1..=missing {
+                            let fill = Bar::flat(last_time + i * interval, last_value);
+                            indicator.calculate(state, options, &fill).await;
+                        }
+                    }
+                }
+                _ => warn!(
+                    "Gap for ticker {} cannot be filled without a known interval, carrying on",
+                    ticker
+                ),
+            },
+        }
+    } else if let Some(gap) = gap {
+        state.last_interval = Some(gap);
+    }
+
+    let result = indicator.calculate(state, options, bar).await;
+    if !late {
+        state.last_time = Some(time);
+        state.last_value = Some(bar.value);
+    }
+    result
+}
+
 #[async_trait]
 impl Handler for IndicatorHandler {
     #[instrument(skip(self))]
@@ -141,9 +924,23 @@ impl Handler for IndicatorHandler {
         debug!("Init request received: {:?}", r);
         match IndicatorOptions::from_proto_options(&r.options) {
             Ok(options) => {
+                self.indicator = indicators::for_type(&options.indicator_type);
                 self.options = options;
+                self.end_task();
+                self.task_id = r.task_id.clone();
+                self.task_started = true;
+                self.start_task();
                 self.data.states.clear();
-                self.data.batch_points.clear();
+                self.take_batch_points();
+                self.data.dirty.clear();
+                self.data.journals.clear();
+                if let Err(e) = self.load_states().await {
+                    error!("Failed to load stored state: {}", e);
+                    return Ok(InitResponse {
+                        success: false,
+                        error: e.to_string(),
+                    });
+                }
                 Ok(InitResponse {
                     success: true,
                     error: String::new(),
@@ -162,7 +959,7 @@ impl Handler for IndicatorHandler {
     #[instrument(skip(self))]
     async fn snapshot(&self) -> io::Result<SnapshotResponse> {
         debug!("Snapshot request received");
-        let snapshot = serde_json::to_vec(&self.data).map_err(|e| {
+        let snapshot = snapshot::encode(&self.options, &self.data.states).map_err(|e| {
             error!("Failed to serialize state: {}", e);
             io::Error::new(io::ErrorKind::Other, e)
         })?;
@@ -172,10 +969,14 @@ impl Handler for IndicatorHandler {
     #[instrument(skip(self, req))]
     async fn restore(&mut self, req: &RestoreRequest) -> io::Result<RestoreResponse> {
         debug!("Restore request received");
-        match serde_json::from_slice(&req.snapshot) {
-            Ok(data) => {
-                self.data = data;
-                self.data.batch_points.clear(); // Clear batch points on restore
+        match snapshot::decode(&req.snapshot, &self.options) {
+            Ok(states) => {
+                self.data.states = states;
+                self.take_batch_points(); // Clear batch points on restore
+                self.data.journals.clear();
+
+                // Bring the state store in line with the restored snapshot on the next flush
+                self.data.dirty = self.data.states.keys().cloned().collect();
                 Ok(RestoreResponse {
                     success: true,
                     error: String::new(),
@@ -195,44 +996,105 @@ impl Handler for IndicatorHandler {
     async fn begin_batch(&mut self, begin: &BeginBatch) -> io::Result<()> {
         debug!("BeginBatch called: {:?}", begin);
 
-        // Store BeginBatch for later use
+        if self.begin_batch.is_some() {
+            warn!("BeginBatch received before the previous batch ended, discarding its points");
+        }
+
+        // Store BeginBatch for later use, it is forwarded in end_batch once the
+        // output of the batch is known
         self.begin_batch = Some(begin.clone());
 
         // Reset state for new batch
-        self.data.batch_points.clear();
+        self.take_batch_points();
+        self.data.truncated = 0;
+        self.data.rejected.clear();
 
         debug!("State reset for new batch");
-        debug!("Sending EndBatch response");
-        self.send_response(Response {
-            message: Some(response::Message::Begin(begin.clone())),
-        })
-        .await
-        .map_err(|e| {
-            error!("Failed to send BeginBatch response: {}", e);
-            io::Error::new(io::ErrorKind::Other, e)
-        })?;
 
         Ok(())
     }
 
-    #[instrument(skip(self, p), fields(point_name = %p.name, point_time = %p.time))]
+    #[instrument(level = "trace", skip(self, p), fields(point_name = %p.name, point_time = %p.time))]
     async fn point(&mut self, p: &Point) -> io::Result<()> {
-        debug!("Processing point: {:?}", p);
+        trace!("Processing point: {:?}", p);
 
-        if let Some(ticker) = p.tags.get(&self.options.ticker_field) {
-            if let Some(value) = p.fields_double.get(&self.options.field) {
-                debug!("Valid point data - ticker: {}, value: {}", ticker, value);
-                self.data.batch_points.push(p.clone());
-                debug!(
-                    "Added point to batch. Current batch size: {}",
-                    self.data.batch_points.len()
-                );
-            } else {
-                warn!("Missing value for ticker: {}", ticker);
+        if self.begin_batch.is_none() {
+            warn!("Point received outside of a batch, ignoring it");
+            return Ok(());
+        }
+
+        self.count(|m| {
+            m.points_received.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let rejected = match p.tags.get(&self.options.ticker_field) {
+            Some(ticker) => match Bar::from_point(p, &self.options) {
+                Some(bar) => {
+                    trace!("Valid point data - ticker: {}, bar: {:?}", ticker, bar);
+                    None
+                }
+                None => Some(DropReason::MissingField),
+            },
+            None => Some(DropReason::MissingTicker),
+        };
+
+        if let Some(reason) = rejected {
+            match self.options.on_reject {
+                RejectPolicy::Drop => {
+                    warn!("Dropping point rejected for {}", reason.label());
+                    self.count(|m| m.point_dropped(reason));
+                    return Ok(());
+                }
+                RejectPolicy::Summary => {
+                    debug!("Counting point rejected for {}", reason.label());
+                    self.count(|m| m.point_dropped(reason));
+                    *self.data.rejected.entry(reason).or_default() += 1;
+                    return Ok(());
+                }
+                RejectPolicy::Pass => debug!("Passing point rejected for {}", reason.label()),
+            }
+        }
+
+        let limit = self.options.max_batch_points;
+        if limit > 0 && self.data.batch_points.len() >= limit {
+            match self.options.overflow_policy {
+                OverflowPolicy::Error => {
+                    error!("Batch exceeds max_batch_points {}, discarding it", limit);
+                    self.take_batch_points();
+                    self.begin_batch = None;
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("Batch exceeds max_batch_points {}", limit),
+                    ));
+                }
+                OverflowPolicy::Truncate => {
+                    self.data.truncated += 1;
+                    self.count(|m| m.point_dropped(DropReason::Overflow));
+                    return Ok(());
+                }
+                OverflowPolicy::Stream => {
+                    self.stream_buffered().await.map_err(|e| {
+                        error!("Failed to stream batch: {}", e);
+                        io::Error::new(io::ErrorKind::Other, e)
+                    })?;
+                }
             }
+        }
+
+        if let Some(reason) = rejected {
+            let mut point = p.clone();
+            point.fields_string.insert(
+                format!("{}_error", self.options.as_field),
+                reason.label().to_string(),
+            );
+            self.buffer_point(&point);
         } else {
-            warn!("Missing ticker in point tags");
+            self.buffer_point(p);
         }
+        trace!(
+            "Added point to batch. Current batch size: {}",
+            self.data.batch_points.len()
+        );
 
         Ok(())
     }
@@ -241,77 +1103,51 @@ impl Handler for IndicatorHandler {
     async fn end_batch(&mut self, end: &EndBatch) -> io::Result<()> {
         debug!("EndBatch called: {:?}", end);
 
-        // Validate that there are batch points to process, implying that BeginBatch was sent
-        if self.data.batch_points.is_empty() {
-            error!("Attempted to send EndBatch without any points being processed. Ensure BeginBatch was sent.");
+        let Some(begin) = self.begin_batch.take() else {
+            error!("Received EndBatch without a preceding BeginBatch");
             return Err(io::Error::new(
                 io::ErrorKind::Other,
                 "EndBatch called without BeginBatch.",
             ));
-        }
+        };
 
-        debug!("Sending beginBatch");
-        self.send_response(Response {
-            message: Some(response::Message::Begin(self.begin_batch.clone().unwrap())),
-        })
-        .await
-        .map_err(|e| {
-            error!("Failed to send point response: {}", e);
+        let points = self.take_batch_points();
+        let mut output = self.process_points(points).await.map_err(|e| {
+            error!("Failed to process batch: {}", e);
             io::Error::new(io::ErrorKind::Other, e)
         })?;
 
-        // Collect ticker, value, and timestamp to avoid borrowing conflicts
-        let data_to_process: Vec<(String, f64, i64)> = self
-            .data
-            .batch_points
-            .iter()
-            .filter_map(|p| {
-                let ticker = p.tags.get(&self.options.ticker_field)?.clone();
-                let value = p.fields_double.get(&self.options.field).cloned()?;
-                Some((ticker, value, p.time)) // Include the timestamp
-            })
-            .collect();
+        self.count(|m| {
+            m.batches.fetch_add(1, Ordering::Relaxed);
+        });
 
-        // Process the collected data
-        for (ticker, value, timestamp) in data_to_process {
-            let indicator_value = self.calculate_indicator(&ticker, value).await.unwrap();
+        // A summary of zero rejections does not keep an empty batch from being suppressed
+        if self.options.on_reject == RejectPolicy::Summary
+            && !(output.is_empty() && self.options.suppress_empty && self.data.rejected.is_empty())
+        {
+            let summary = self.rejection_summary(&begin, end);
+            output.push(summary);
+        }
 
-            // Find the corresponding point and modify it
-            if let Some(p) = self
-                .data
-                .batch_points
-                .iter()
-                .find(|p| p.tags.get(&self.options.ticker_field) == Some(&ticker))
-            {
-                let mut new_point = p.clone();
-                new_point
-                    .fields_double
-                    .insert(self.options.as_field.clone(), indicator_value);
-                new_point.time = timestamp; // Set the original timestamp
-
-                // Send the updated point to Kapacitor
-                debug!("Sending point: {:?}", new_point);
-                self.send_response(Response {
-                    message: Some(response::Message::Point(new_point)),
-                })
-                .await
-                .map_err(|e| {
-                    error!("Failed to send point response: {}", e);
-                    io::Error::new(io::ErrorKind::Other, e)
-                })?;
-            }
+        if self.data.truncated > 0 {
+            warn!(
+                "Dropped {} points beyond max_batch_points {} from batch",
+                self.data.truncated, self.options.max_batch_points
+            );
+            self.data.truncated = 0;
         }
 
-        // Send the EndBatch to Kapacitor
-        debug!("Sending EndBatch");
-        self.send_response(Response {
-            message: Some(response::Message::End(end.clone())),
-        })
-        .await
-        .map_err(|e| {
-            error!("Failed to send EndBatch response: {}", e);
-            io::Error::new(io::ErrorKind::Other, e)
-        })?;
+        if output.is_empty() && self.options.suppress_empty {
+            debug!("Suppressing empty batch");
+        } else {
+            self.send_batch(begin, output, end).await.map_err(|e| {
+                error!("Failed to send batch: {}", e);
+                io::Error::new(io::ErrorKind::Other, e)
+            })?;
+        }
+
+        self.evict_states().await;
+        self.flush_states().await;
 
         Ok(())
     }
@@ -319,11 +1155,22 @@ impl Handler for IndicatorHandler {
     #[instrument(skip(self))]
     async fn stop(&mut self) {
         debug!("Stop called, closing agent responses");
+        self.take_batch_points();
         let _ = self.responses.lock().await.close();
         debug!("IndicatorHandler stopped");
     }
 }
 
+impl Drop for IndicatorHandler {
+    fn drop(&mut self) {
+        self.take_batch_points();
+        self.end_task();
+        if let Some(metrics) = &self.metrics {
+            metrics.active_agents.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
 impl std::fmt::Debug for IndicatorHandler {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("IndicatorHandler")
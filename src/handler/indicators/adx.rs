@@ -0,0 +1,103 @@
+use super::{detail, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Wilder's Average Directional Index with the +DI and -DI it is derived from.
+///
+/// The directional indicators are available from the `period + 1`th bar on and
+/// written to `<as>_plus_di` and `<as>_minus_di`, the ADX from the
+/// `2 * period`th bar on. The smoothed values are seeded as Wilder and
+/// StockCharts do, with the sum of the first `period` values, so the early
+/// values differ slightly from TA-Lib, which sums only `period - 1` of them.
+pub struct Adx;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AdxState {
+    /// The previous bar, true range and directional movement need its high, low and close.
+    previous: Option<Bar>,
+    /// Number of true ranges seen so far.
+    samples: u32,
+    /// Wilder-smoothed true range, a plain sum until `period` samples are seen.
+    true_range: f64,
+    plus_dm: f64,
+    minus_dm: f64,
+    /// Sum of the DX values before the ADX is seeded with their average.
+    dx_sum: f64,
+    dx_count: u32,
+    adx: Option<f64>,
+}
+
+#[async_trait]
+impl Indicator for Adx {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let period = options.period as f64;
+        let s = detail::<AdxState>(state);
+
+        let Some(previous) = s.previous.replace(*bar) else {
+            return Vec::new();
+        };
+
+        let true_range = (bar.high - bar.low)
+            .max((bar.high - previous.close).abs())
+            .max((bar.low - previous.close).abs());
+        let up = bar.high - previous.high;
+        let down = previous.low - bar.low;
+        let plus_dm = if up > down && up > 0.0 { up } else { 0.0 };
+        let minus_dm = if down > up && down > 0.0 { down } else { 0.0 };
+
+        s.samples += 1;
+        if s.samples <= options.period {
+            s.true_range += true_range;
+            s.plus_dm += plus_dm;
+            s.minus_dm += minus_dm;
+            if s.samples < options.period {
+                return Vec::new();
+            }
+        } else {
+            s.true_range += true_range - s.true_range / period;
+            s.plus_dm += plus_dm - s.plus_dm / period;
+            s.minus_dm += minus_dm - s.minus_dm / period;
+        }
+
+        let (plus_di, minus_di) = if s.true_range > 0.0 {
+            (
+                100.0 * s.plus_dm / s.true_range,
+                100.0 * s.minus_dm / s.true_range,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        let dx = if plus_di + minus_di > 0.0 {
+            100.0 * (plus_di - minus_di).abs() / (plus_di + minus_di)
+        } else {
+            0.0
+        };
+
+        match s.adx {
+            Some(adx) => s.adx = Some((adx * (period - 1.0) + dx) / period),
+            None => {
+                s.dx_sum += dx;
+                s.dx_count += 1;
+                if s.dx_count == options.period {
+                    s.adx = Some(s.dx_sum / period);
+                }
+            }
+        }
+
+        let mut outputs = vec![
+            Output::named("plus_di", plus_di),
+            Output::named("minus_di", minus_di),
+        ];
+        if let Some(adx) = s.adx {
+            outputs.push(Output::main(adx));
+        }
+        outputs
+    }
+}
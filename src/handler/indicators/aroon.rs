@@ -0,0 +1,58 @@
+use super::{detail, window::Window, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Aroon Up and Down over the last `period + 1` bars and their difference, the Aroon Oscillator.
+///
+/// Up and Down are written to `<as>_up` and `<as>_down`, the oscillator to the
+/// `as` field. If the highest high or lowest low occurs more than once, the
+/// most recent occurrence counts, as in TA-Lib.
+pub struct Aroon;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AroonState {
+    highs: Window,
+    lows: Window,
+}
+
+#[async_trait]
+impl Indicator for Aroon {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let period = options.period as usize;
+        let s = detail::<AroonState>(state);
+
+        s.highs.push(bar.high, period + 1);
+        if !s.lows.push(bar.low, period + 1) {
+            return Vec::new();
+        }
+
+        // Positions in the window, 0 being `period` bars ago and `period` the current bar
+        let highest = most_recent_extreme(&s.highs, |candidate, best| candidate >= best);
+        let lowest = most_recent_extreme(&s.lows, |candidate, best| candidate <= best);
+        let up = 100.0 * highest as f64 / period as f64;
+        let down = 100.0 * lowest as f64 / period as f64;
+
+        vec![
+            Output::named("up", up),
+            Output::named("down", down),
+            Output::main(up - down),
+        ]
+    }
+}
+
+fn most_recent_extreme(window: &Window, replaces: impl Fn(f64, f64) -> bool) -> usize {
+    let mut best = (0, f64::NAN);
+    for (i, value) in window.iter().enumerate() {
+        if i == 0 || replaces(value, best.1) {
+            best = (i, value);
+        }
+    }
+    best.0
+}
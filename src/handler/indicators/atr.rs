@@ -0,0 +1,40 @@
+use super::Bar;
+use serde::{Deserialize, Serialize};
+
+/// Wilder's Average True Range, used by the indicators that size bands by volatility.
+///
+/// As in TA-Lib, the first bar only provides the previous close, the ATR is
+/// seeded with the mean true range of the next `period` bars and smoothed
+/// with `1 / period` afterwards.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AtrState {
+    previous_close: Option<f64>,
+    /// Number of true ranges seen so far.
+    samples: u32,
+    /// Sum of the true ranges before the ATR is seeded.
+    sum: f64,
+    atr: Option<f64>,
+}
+
+impl AtrState {
+    /// Advances the ATR by one bar and returns it once `period` true ranges were seen.
+    pub fn next(&mut self, bar: &Bar, period: u32) -> Option<f64> {
+        let previous_close = self.previous_close.replace(bar.close)?;
+        let true_range = (bar.high - bar.low)
+            .max((bar.high - previous_close).abs())
+            .max((bar.low - previous_close).abs());
+
+        let n = period as f64;
+        match self.atr {
+            Some(atr) => self.atr = Some((atr * (n - 1.0) + true_range) / n),
+            None => {
+                self.samples += 1;
+                self.sum += true_range;
+                if self.samples == period {
+                    self.atr = Some(self.sum / n);
+                }
+            }
+        }
+        self.atr
+    }
+}
@@ -0,0 +1,49 @@
+use super::{detail, window::Window, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Lambert's Commodity Channel Index over `period` bars, as TA-Lib `CCI`.
+///
+/// The deviation of the typical price `(high + low + close) / 3` from its mean,
+/// in units of 0.015 times the mean absolute deviation. Available from the
+/// `period`th bar on, 0 while the typical price does not deviate at all.
+pub struct Cci;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CciState {
+    typical_prices: Window,
+}
+
+#[async_trait]
+impl Indicator for Cci {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let period = options.period as usize;
+        let s = detail::<CciState>(state);
+
+        let typical_price = (bar.high + bar.low + bar.close) / 3.0;
+        if !s.typical_prices.push(typical_price, period) {
+            return Vec::new();
+        }
+
+        let mean = s.typical_prices.iter().sum::<f64>() / period as f64;
+        let mean_deviation = s
+            .typical_prices
+            .iter()
+            .map(|tp| (tp - mean).abs())
+            .sum::<f64>()
+            / period as f64;
+        let cci = if mean_deviation > 0.0 {
+            (typical_price - mean) / (0.015 * mean_deviation)
+        } else {
+            0.0
+        };
+        vec![Output::main(cci)]
+    }
+}
@@ -0,0 +1,112 @@
+use super::{detail, window::Window, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Rate of Change, the percentage change of the value over `period` bars, as TA-Lib `ROC`.
+///
+/// Available from the `period + 1`th bar on. A change from zero is reported as 0.
+pub struct Roc;
+
+/// Momentum, the difference of the value to the value `period` bars ago, as TA-Lib `MOM`.
+///
+/// Available from the `period + 1`th bar on.
+pub struct Mom;
+
+/// The simple return `value / previous - 1` over `period` bars, as a fraction.
+///
+/// Available from the `period + 1`th bar on. No return is reported from zero.
+pub struct Return;
+
+/// The log return `ln(value / previous)` over `period` bars.
+///
+/// Available from the `period + 1`th bar on. No return is reported unless
+/// both values are positive.
+pub struct LogReturn;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChangeState {
+    values: Window,
+}
+
+impl ChangeState {
+    /// Appends a value and returns the value `period` bars before it, once known.
+    fn push(&mut self, value: f64, period: u32) -> Option<f64> {
+        if self.values.push(value, period as usize + 1) {
+            self.values.iter().next()
+        } else {
+            None
+        }
+    }
+}
+
+#[async_trait]
+impl Indicator for Roc {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let Some(previous) = detail::<ChangeState>(state).push(bar.value, options.period) else {
+            return Vec::new();
+        };
+        let roc = if previous != 0.0 {
+            (bar.value / previous - 1.0) * 100.0
+        } else {
+            0.0
+        };
+        vec![Output::main(roc)]
+    }
+}
+
+#[async_trait]
+impl Indicator for Mom {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let Some(previous) = detail::<ChangeState>(state).push(bar.value, options.period) else {
+            return Vec::new();
+        };
+        vec![Output::main(bar.value - previous)]
+    }
+}
+
+#[async_trait]
+impl Indicator for Return {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        match detail::<ChangeState>(state).push(bar.value, options.period) {
+            Some(previous) if previous != 0.0 => vec![Output::main(bar.value / previous - 1.0)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Indicator for LogReturn {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        match detail::<ChangeState>(state).push(bar.value, options.period) {
+            Some(previous) if previous > 0.0 && bar.value > 0.0 => {
+                vec![Output::main((bar.value / previous).ln())]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
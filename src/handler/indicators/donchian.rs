@@ -0,0 +1,43 @@
+use super::{detail, window::Window, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Donchian Channels, the highest high and lowest low of the last `period` bars, including the current one.
+///
+/// The bands are written to `<as>_upper` and `<as>_lower` and their midpoint
+/// to the `as` field, once `period` bars were seen.
+pub struct Donchian;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DonchianState {
+    highs: Window,
+    lows: Window,
+}
+
+#[async_trait]
+impl Indicator for Donchian {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let period = options.period as usize;
+        let s = detail::<DonchianState>(state);
+
+        s.highs.push(bar.high, period);
+        if !s.lows.push(bar.low, period) {
+            return Vec::new();
+        }
+
+        let upper = s.highs.max();
+        let lower = s.lows.min();
+        vec![
+            Output::named("upper", upper),
+            Output::named("lower", lower),
+            Output::main((upper + lower) / 2.0),
+        ]
+    }
+}
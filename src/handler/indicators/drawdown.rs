@@ -0,0 +1,55 @@
+use super::{detail, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Drawdown from the running peak of the value, the high-water mark.
+///
+/// The drawdown `1 - value / peak` is a fraction between 0 at the peak and 1
+/// for a value of zero. The peak is written to `<as>_peak`, the largest
+/// drawdown seen to `<as>_max` and the number of bars since the peak to
+/// `<as>_duration`. The peak is kept for as long as the ticker's state, across
+/// batches and snapshots, and no drawdown is reported while it is not positive.
+pub struct Drawdown;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DrawdownState {
+    peak: Option<f64>,
+    max_drawdown: f64,
+    duration: i64,
+}
+
+#[async_trait]
+impl Indicator for Drawdown {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        _options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let s = detail::<DrawdownState>(state);
+
+        let peak = match s.peak {
+            Some(peak) if peak >= bar.value => {
+                s.duration += 1;
+                peak
+            }
+            _ => {
+                s.duration = 0;
+                bar.value
+            }
+        };
+        s.peak = Some(peak);
+
+        let mut outputs = vec![Output::named("peak", peak)];
+        if peak > 0.0 {
+            let drawdown = 1.0 - bar.value / peak;
+            s.max_drawdown = s.max_drawdown.max(drawdown);
+            outputs.push(Output::main(drawdown));
+            outputs.push(Output::named("max", s.max_drawdown));
+            outputs.push(Output::named_int("duration", s.duration));
+        }
+        outputs
+    }
+}
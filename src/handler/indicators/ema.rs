@@ -1,19 +1,29 @@
-use super::Indicator;
-use crate::handler::config::IndicatorState;
+use super::{Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
 use async_trait::async_trait;
 
 pub struct Ema;
 
+/// Advances an EMA over `period` bars by one value, seeding it with the first value.
+pub fn next(ema: Option<f64>, period: usize, value: f64) -> f64 {
+    let alpha = 2.0 / (period as f64 + 1.0);
+    match ema {
+        Some(ema) => alpha * value + (1.0 - alpha) * ema,
+        None => value,
+    }
+}
+
 #[async_trait]
 impl Indicator for Ema {
-    async fn calculate(&mut self, state: &mut IndicatorState, period: usize, value: f64) -> f64 {
-        let alpha = 2.0 / (period as f64 + 1.0);
-        let new_ema = match state.current_value {
-            Some(ema) => alpha * value + (1.0 - alpha) * ema,
-            None => value,
-        };
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        let new_ema = next(state.current_value, options.period as usize, bar.value);
         state.current_value = Some(new_ema);
         state.count += 1;
-        new_ema
+        vec![Output::main(new_ema)]
     }
 }
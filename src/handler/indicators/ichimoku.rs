@@ -0,0 +1,62 @@
+use super::{detail, window::Window, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Ichimoku Kinko Hyo over the short, medium and long period, usually 9, 26 and 52 bars.
+///
+/// Tenkan-sen and Kijun-sen, the midpoints of the range of the short and
+/// medium period, are written to `<as>_tenkan` and `<as>_kijun`. Senkou Span A,
+/// their average, and Senkou Span B, the midpoint of the range of the long
+/// period, are plotted `displacement` bars ahead in `<as>_senkou_a` and
+/// `<as>_senkou_b`. Chikou Span, the close, is plotted `displacement` bars
+/// behind in `<as>_chikou`.
+pub struct Ichimoku;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IchimokuState {
+    highs: Window,
+    lows: Window,
+}
+
+#[async_trait]
+impl Indicator for Ichimoku {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let [short, medium, long] = options.periods().map(|p| p as usize);
+        let displacement = options.displacement() as i64;
+        let s = detail::<IchimokuState>(state);
+
+        s.highs.push(bar.high, long);
+        s.lows.push(bar.low, long);
+        let midpoint = |n: usize| {
+            (s.highs.len() >= n).then(|| {
+                let high = s.highs.recent(n).fold(f64::NEG_INFINITY, f64::max);
+                let low = s.lows.recent(n).fold(f64::INFINITY, f64::min);
+                (high + low) / 2.0
+            })
+        };
+
+        let mut outputs = vec![Output::named("chikou", bar.close).shifted(-displacement)];
+        let tenkan = midpoint(short);
+        let kijun = midpoint(medium);
+        if let Some(tenkan) = tenkan {
+            outputs.push(Output::named("tenkan", tenkan));
+        }
+        if let Some(kijun) = kijun {
+            outputs.push(Output::named("kijun", kijun));
+        }
+        if let (Some(tenkan), Some(kijun)) = (tenkan, kijun) {
+            outputs.push(Output::named("senkou_a", (tenkan + kijun) / 2.0).shifted(displacement));
+        }
+        if let Some(senkou_b) = midpoint(long) {
+            outputs.push(Output::named("senkou_b", senkou_b).shifted(displacement));
+        }
+        outputs
+    }
+}
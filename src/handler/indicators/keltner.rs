@@ -0,0 +1,41 @@
+use super::{atr::AtrState, detail, ema, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Keltner Channels, an EMA of the close over `period` bars with bands `multiplier`
+/// times the ATR over `atr_period` bars above and below it.
+///
+/// The EMA is written to the `as` field from the first bar on, seeded like the
+/// `EMA` indicator. The bands are written to `<as>_upper` and `<as>_lower`
+/// once the ATR is available.
+pub struct Keltner;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeltnerState {
+    ema: Option<f64>,
+    atr: AtrState,
+}
+
+#[async_trait]
+impl Indicator for Keltner {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let s = detail::<KeltnerState>(state);
+
+        let middle = ema::next(s.ema, options.period as usize, bar.close);
+        s.ema = Some(middle);
+
+        let mut outputs = vec![Output::main(middle)];
+        if let Some(atr) = s.atr.next(bar, options.atr_period) {
+            outputs.push(Output::named("upper", middle + options.multiplier * atr));
+            outputs.push(Output::named("lower", middle - options.multiplier * atr));
+        }
+        outputs
+    }
+}
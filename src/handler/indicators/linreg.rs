@@ -0,0 +1,83 @@
+use super::{detail, window::Window, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState, RegressionX};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Least-squares line through the last `period` values, as TA-Lib `LINEARREG`.
+///
+/// x is the bar index or, with `regression_x` `time`, the seconds of
+/// `Point::time` since the oldest point of the window. The fitted value at the
+/// latest point, the least-squares moving average, is the main output; the
+/// slope per x, the intercept at the oldest point, R² and the value
+/// `forecast` bars ahead are written to `<as>_slope`, `<as>_intercept`,
+/// `<as>_r2` and `<as>_forecast`. On time, a bar ahead is the average spacing
+/// of the window. Available once the window is full, for a `period` of at
+/// least 2.
+pub struct LinReg;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinRegState {
+    values: Window,
+    times: VecDeque<i64>,
+}
+
+#[async_trait]
+impl Indicator for LinReg {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let period = options.period as usize;
+        let s = detail::<LinRegState>(state);
+
+        s.times.push_back(bar.time);
+        while s.times.len() > period {
+            s.times.pop_front();
+        }
+        if !s.values.push(bar.value, period) {
+            return Vec::new();
+        }
+
+        let first = s.times[0];
+        let xs: Vec<f64> = match options.regression_x {
+            RegressionX::Index => (0..period).map(|i| i as f64).collect(),
+            RegressionX::Time => s.times.iter().map(|&t| (t - first) as f64 / 1e9).collect(),
+        };
+        let n = period as f64;
+        let mean_x = xs.iter().sum::<f64>() / n;
+        let mean_y = s.values.iter().sum::<f64>() / n;
+        let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+        for (x, y) in xs.iter().zip(s.values.iter()) {
+            sxx += (x - mean_x).powi(2);
+            sxy += (x - mean_x) * (y - mean_y);
+            syy += (y - mean_y).powi(2);
+        }
+        if sxx <= 0.0 {
+            return Vec::new();
+        }
+
+        let slope = sxy / sxx;
+        let intercept = mean_y - slope * mean_x;
+        let last = xs[period - 1];
+        let step = last / (n - 1.0);
+        let r2 = if syy > 0.0 {
+            sxy * sxy / (sxx * syy)
+        } else {
+            0.0
+        };
+        vec![
+            Output::main(intercept + slope * last),
+            Output::named("slope", slope),
+            Output::named("intercept", intercept),
+            Output::named("r2", r2),
+            Output::named(
+                "forecast",
+                intercept + slope * (last + step * f64::from(options.forecast)),
+            ),
+        ]
+    }
+}
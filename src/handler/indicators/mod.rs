@@ -1,10 +1,270 @@
-use super::config::IndicatorState;
+use super::config::{IndicatorOptions, IndicatorState, IndicatorType};
 use async_trait::async_trait;
+use kapacitor_udf::proto::Point;
+use serde::{Deserialize, Serialize};
 
+pub mod adx;
+pub mod aroon;
+pub mod atr;
+pub mod cci;
+pub mod change;
+pub mod donchian;
+pub mod drawdown;
 pub mod ema;
+pub mod ichimoku;
+pub mod keltner;
+pub mod linreg;
+pub mod sar;
 pub mod sma;
+pub mod stats;
+pub mod supertrend;
+pub mod trix;
+pub mod ultosc;
+pub mod volatility;
+pub mod window;
 
+/// The input of an indicator for a single point.
+///
+/// Indicators on a single field see it as a flat bar with `open`, `high`,
+/// `low` and `close` equal to `value`. Indicators on high/low/close fields see
+/// the close as `value`, and also as `open` unless they read the open field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Bar {
+    pub time: i64,
+    pub value: f64,
+    #[serde(default)]
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+impl Bar {
+    /// Creates a bar whose high, low and close are all `value`.
+    pub fn flat(time: i64, value: f64) -> Self {
+        Bar {
+            time,
+            value,
+            open: value,
+            high: value,
+            low: value,
+            close: value,
+        }
+    }
+
+    /// Reads the input fields of the configured indicator from a point.
+    ///
+    /// Returns `None` if any of the fields is missing.
+    pub fn from_point(point: &Point, options: &IndicatorOptions) -> Option<Self> {
+        let field = |name: &str| point.fields_double.get(name).copied();
+        if options.indicator_type.uses_ohlc() {
+            let close = field(&options.close_field)?;
+            let open = if options.indicator_type.uses_open() {
+                field(&options.open_field)?
+            } else {
+                close
+            };
+            Some(Bar {
+                time: point.time,
+                value: close,
+                open,
+                high: field(&options.high_field)?,
+                low: field(&options.low_field)?,
+                close,
+            })
+        } else {
+            Some(Bar::flat(point.time, field(&options.field)?))
+        }
+    }
+}
+
+/// A value calculated by an indicator, written to the `as` field or to `<as>_<suffix>`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Output {
+    /// Empty for the `as` field itself.
+    pub suffix: &'static str,
+    pub value: OutputValue,
+    /// Number of bars the value is plotted ahead of the bar it was calculated
+    /// for, negative for bars behind it.
+    pub shift: i64,
+}
+
+/// The value of an output, written to a double or an integer field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputValue {
+    Double(f64),
+    Int(i64),
+}
+
+impl Output {
+    /// The main value of the indicator, written to the `as` field.
+    pub fn main(value: f64) -> Self {
+        Output::named("", value)
+    }
+
+    /// An additional value of the indicator, written to `<as>_<suffix>`.
+    pub fn named(suffix: &'static str, value: f64) -> Self {
+        Output {
+            suffix,
+            value: OutputValue::Double(value),
+            shift: 0,
+        }
+    }
+
+    /// An additional integer value of the indicator, such as a direction, written to `<as>_<suffix>`.
+    pub fn named_int(suffix: &'static str, value: i64) -> Self {
+        Output {
+            suffix,
+            value: OutputValue::Int(value),
+            shift: 0,
+        }
+    }
+
+    /// Plots the value `bars` bars ahead of the bar it was calculated for, or behind it if negative.
+    pub fn shifted(self, bars: i64) -> Self {
+        Output {
+            shift: bars,
+            ..self
+        }
+    }
+
+    /// Returns the name of the output field for the given `as` field.
+    pub fn field_name(&self, as_field: &str) -> String {
+        if self.suffix.is_empty() {
+            as_field.to_string()
+        } else {
+            format!("{}_{}", as_field, self.suffix)
+        }
+    }
+
+    /// Writes the output into the matching field of a point.
+    pub fn write_to(&self, point: &mut Point, as_field: &str) {
+        let name = self.field_name(as_field);
+        match self.value {
+            OutputValue::Double(value) => {
+                point.fields_double.insert(name, value);
+            }
+            OutputValue::Int(value) => {
+                point.fields_int.insert(name, value);
+            }
+        }
+    }
+}
+
+/// Calculates an indicator one bar at a time.
+///
+/// An indicator returns no outputs, or only some of them, while it has not
+/// seen enough bars yet.
 #[async_trait]
 pub trait Indicator: Send + Sync {
-    async fn calculate(&mut self, state: &mut IndicatorState, period: usize, value: f64) -> f64;
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output>;
+}
+
+/// Per-ticker state of the indicators that need more than the generic fields of `IndicatorState`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum IndicatorDetail {
+    Adx(adx::AdxState),
+    Aroon(aroon::AroonState),
+    Sar(sar::SarState),
+    SuperTrend(supertrend::SuperTrendState),
+    Keltner(keltner::KeltnerState),
+    Donchian(donchian::DonchianState),
+    Cci(cci::CciState),
+    Change(change::ChangeState),
+    Trix(trix::TrixState),
+    UltOsc(ultosc::UltOscState),
+    Ichimoku(ichimoku::IchimokuState),
+    Volatility(volatility::VolatilityState),
+    Drawdown(drawdown::DrawdownState),
+    LinReg(linreg::LinRegState),
+}
+
+/// Typed access to the variant of `IndicatorDetail` holding an indicator's state.
+trait Detail: Default + Into<IndicatorDetail> {
+    fn get(detail: &mut IndicatorDetail) -> Option<&mut Self>;
+}
+
+macro_rules! detail_variant {
+    ($variant:ident, $state:ty) => {
+        impl From<$state> for IndicatorDetail {
+            fn from(state: $state) -> Self {
+                IndicatorDetail::$variant(state)
+            }
+        }
+
+        impl Detail for $state {
+            fn get(detail: &mut IndicatorDetail) -> Option<&mut Self> {
+                match detail {
+                    IndicatorDetail::$variant(state) => Some(state),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+detail_variant!(Adx, adx::AdxState);
+detail_variant!(Aroon, aroon::AroonState);
+detail_variant!(Sar, sar::SarState);
+detail_variant!(SuperTrend, supertrend::SuperTrendState);
+detail_variant!(Keltner, keltner::KeltnerState);
+detail_variant!(Donchian, donchian::DonchianState);
+detail_variant!(Cci, cci::CciState);
+detail_variant!(Change, change::ChangeState);
+detail_variant!(Trix, trix::TrixState);
+detail_variant!(UltOsc, ultosc::UltOscState);
+detail_variant!(Ichimoku, ichimoku::IchimokuState);
+detail_variant!(Volatility, volatility::VolatilityState);
+detail_variant!(Drawdown, drawdown::DrawdownState);
+detail_variant!(LinReg, linreg::LinRegState);
+
+/// Returns the detail state of type `T`, replacing the state of any other indicator.
+fn detail<T: Detail>(state: &mut IndicatorState) -> &mut T {
+    if state.detail.as_mut().and_then(T::get).is_none() {
+        state.detail = Some(T::default().into());
+    }
+    state
+        .detail
+        .as_mut()
+        .and_then(T::get)
+        .expect("detail state was just initialized")
+}
+
+/// Creates the indicator implementation for the given type.
+pub fn for_type(indicator_type: &IndicatorType) -> Box<dyn Indicator + Send> {
+    match indicator_type {
+        IndicatorType::EMA => Box::new(ema::Ema),
+        IndicatorType::SMA => Box::new(sma::Sma),
+        IndicatorType::ADX => Box::new(adx::Adx),
+        IndicatorType::AROON => Box::new(aroon::Aroon),
+        IndicatorType::SAR => Box::new(sar::Sar),
+        IndicatorType::SUPERTREND => Box::new(supertrend::SuperTrend),
+        IndicatorType::KELTNER => Box::new(keltner::Keltner),
+        IndicatorType::DONCHIAN => Box::new(donchian::Donchian),
+        IndicatorType::CCI => Box::new(cci::Cci),
+        IndicatorType::ROC => Box::new(change::Roc),
+        IndicatorType::MOM => Box::new(change::Mom),
+        IndicatorType::TRIX => Box::new(trix::Trix),
+        IndicatorType::ULTOSC => Box::new(ultosc::UltOsc),
+        IndicatorType::ICHIMOKU => Box::new(ichimoku::Ichimoku),
+        IndicatorType::STDDEV => Box::new(stats::Rolling(stats::Statistic::StdDev)),
+        IndicatorType::VARIANCE => Box::new(stats::Rolling(stats::Statistic::Variance)),
+        IndicatorType::ZSCORE => Box::new(stats::Rolling(stats::Statistic::ZScore)),
+        IndicatorType::PERCENTRANK => Box::new(stats::Rolling(stats::Statistic::PercentRank)),
+        IndicatorType::MIN => Box::new(stats::Rolling(stats::Statistic::Min)),
+        IndicatorType::MAX => Box::new(stats::Rolling(stats::Statistic::Max)),
+        IndicatorType::MEDIAN => Box::new(stats::Rolling(stats::Statistic::Median)),
+        IndicatorType::RETURN => Box::new(change::Return),
+        IndicatorType::LOGRETURN => Box::new(change::LogReturn),
+        IndicatorType::REALIZEDVOL => Box::new(volatility::Realized),
+        IndicatorType::PARKINSON => Box::new(volatility::Parkinson),
+        IndicatorType::GARMANKLASS => Box::new(volatility::GarmanKlass),
+        IndicatorType::DRAWDOWN => Box::new(drawdown::Drawdown),
+        IndicatorType::LINREG => Box::new(linreg::LinReg),
+    }
 }
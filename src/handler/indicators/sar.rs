@@ -0,0 +1,123 @@
+use super::{detail, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Wilder's Parabolic SAR, following TA-Lib `SAR`.
+///
+/// The initial trend is taken from the directional movement between the
+/// first two bars: short if the low fell further than the high rose, long
+/// otherwise. The stop level is available from the second bar on and written
+/// to the `as` field, the trend it belongs to to `<as>_direction` as `1` for
+/// long and `-1` for short.
+pub struct Sar;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SarState {
+    previous: Option<Bar>,
+    /// `None` until the initial trend is known.
+    trend: Option<Trend>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Trend {
+    long: bool,
+    /// The stop level of the next bar.
+    sar: f64,
+    /// Extreme point, the highest high of a long or lowest low of a short trend.
+    extreme: f64,
+    acceleration: f64,
+}
+
+#[async_trait]
+impl Indicator for Sar {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let s = detail::<SarState>(state);
+
+        let Some(previous) = s.previous.replace(*bar) else {
+            return Vec::new();
+        };
+
+        let (trend, previous) = match &mut s.trend {
+            Some(trend) => (trend, previous),
+            None => {
+                let down = previous.low - bar.low;
+                let up = bar.high - previous.high;
+                let long = !(down > 0.0 && down > up);
+                let trend = s.trend.insert(Trend {
+                    long,
+                    sar: if long { previous.low } else { previous.high },
+                    extreme: if long { bar.high } else { bar.low },
+                    acceleration: options.acceleration,
+                });
+                // TA-Lib compares the first stop with the second bar only
+                (trend, *bar)
+            }
+        };
+
+        let sar = trend.step(&previous, bar, options);
+        vec![
+            Output::main(sar),
+            Output::named_int("direction", if trend.long { 1 } else { -1 }),
+        ]
+    }
+}
+
+impl Trend {
+    /// Returns the stop level of the bar, reversing the trend if the bar crosses it,
+    /// and advances the stop level to the next bar.
+    fn step(&mut self, previous: &Bar, bar: &Bar, options: &IndicatorOptions) -> f64 {
+        if self.long {
+            if bar.low <= self.sar {
+                self.long = false;
+                let sar = self.extreme.max(previous.high).max(bar.high);
+                self.acceleration = options.acceleration;
+                self.extreme = bar.low;
+                self.sar = (sar + self.acceleration * (self.extreme - sar))
+                    .max(previous.high)
+                    .max(bar.high);
+                return sar;
+            }
+            let sar = self.sar;
+            if bar.high > self.extreme {
+                self.extreme = bar.high;
+                self.accelerate(options);
+            }
+            self.sar = (sar + self.acceleration * (self.extreme - sar))
+                .min(previous.low)
+                .min(bar.low);
+            sar
+        } else {
+            if bar.high >= self.sar {
+                self.long = true;
+                let sar = self.extreme.min(previous.low).min(bar.low);
+                self.acceleration = options.acceleration;
+                self.extreme = bar.high;
+                self.sar = (sar + self.acceleration * (self.extreme - sar))
+                    .min(previous.low)
+                    .min(bar.low);
+                return sar;
+            }
+            let sar = self.sar;
+            if bar.low < self.extreme {
+                self.extreme = bar.low;
+                self.accelerate(options);
+            }
+            self.sar = (sar + self.acceleration * (self.extreme - sar))
+                .max(previous.high)
+                .max(bar.high);
+            sar
+        }
+    }
+
+    fn accelerate(&mut self, options: &IndicatorOptions) {
+        self.acceleration =
+            (self.acceleration + options.acceleration).min(options.max_acceleration);
+    }
+}
@@ -1,21 +1,29 @@
-use super::Indicator;
-use crate::handler::config::IndicatorState;
+use super::{Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
 use async_trait::async_trait;
 
 pub struct Sma;
 
 #[async_trait]
 impl Indicator for Sma {
-    async fn calculate(&mut self, state: &mut IndicatorState, period: usize, value: f64) -> f64 {
-        state.values.push(value);
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        let period = options.period as usize;
+        state.values.push(bar.value);
         if state.values.len() > period {
             state.values.remove(0);
         }
         state.count += 1;
         if state.values.len() == period {
-            state.values.iter().sum::<f64>() / period as f64
+            vec![Output::main(
+                state.values.iter().sum::<f64>() / period as f64,
+            )]
         } else {
-            value
+            vec![Output::main(bar.value)]
         }
     }
 }
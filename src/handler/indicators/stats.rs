@@ -0,0 +1,91 @@
+use super::{Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+
+/// Statistics over the rolling window of a ticker's values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Statistic {
+    /// Population standard deviation, as TA-Lib `STDDEV`.
+    StdDev,
+    /// Population variance, as TA-Lib `VAR`.
+    Variance,
+    /// Distance of the latest value from the mean in standard deviations, 0 for a constant window.
+    ZScore,
+    /// Percentage of the previous `period` values less than or equal to the latest value.
+    PercentRank,
+    Min,
+    Max,
+    Median,
+}
+
+/// A statistic over the last `period` values, kept in `IndicatorState::values` like `Sma`.
+///
+/// Available once the window is full. `PercentRank` compares the latest value
+/// with the `period` values before it and is available from the
+/// `period + 1`th value on.
+pub struct Rolling(pub Statistic);
+
+#[async_trait]
+impl Indicator for Rolling {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        let period = options.period as usize;
+        let len = match self.0 {
+            Statistic::PercentRank => period + 1,
+            _ => period,
+        };
+        state.values.push(bar.value);
+        if state.values.len() > len {
+            state.values.remove(0);
+        }
+        state.count += 1;
+        if state.values.len() < len {
+            return Vec::new();
+        }
+
+        let values = &state.values;
+        let value = match self.0 {
+            Statistic::StdDev => variance(values).sqrt(),
+            Statistic::Variance => variance(values),
+            Statistic::ZScore => {
+                let deviation = variance(values).sqrt();
+                if deviation > 0.0 {
+                    (bar.value - mean(values)) / deviation
+                } else {
+                    0.0
+                }
+            }
+            Statistic::PercentRank => {
+                let previous = &values[..period];
+                let below = previous.iter().filter(|&&v| v <= bar.value).count();
+                100.0 * below as f64 / period as f64
+            }
+            Statistic::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Statistic::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Statistic::Median => {
+                let mut sorted = values.clone();
+                sorted.sort_by(f64::total_cmp);
+                let middle = sorted.len() / 2;
+                if sorted.len() % 2 == 0 {
+                    (sorted[middle - 1] + sorted[middle]) / 2.0
+                } else {
+                    sorted[middle]
+                }
+            }
+        };
+        vec![Output::main(value)]
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn variance(values: &[f64]) -> f64 {
+    let mean = mean(values);
+    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+}
@@ -0,0 +1,79 @@
+use super::{atr::AtrState, detail, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// SuperTrend, bands of `multiplier` times the ATR over `period` bars around the bar's midpoint.
+///
+/// The lower band only rises while the trend is up and the upper band only
+/// falls while it is down. The trend turns up when the close exceeds the upper
+/// band of the previous bar and down when it falls below its lower band,
+/// starting out up. The stop level, the lower band in an uptrend and the upper
+/// band in a downtrend, is written to the `as` field once the ATR is
+/// available, the trend to `<as>_direction` as `1` for up and `-1` for down.
+pub struct SuperTrend;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SuperTrendState {
+    atr: AtrState,
+    /// Final bands and close of the previous bar, `None` before the ATR is available.
+    previous: Option<Bands>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Bands {
+    upper: f64,
+    lower: f64,
+    close: f64,
+    up: bool,
+}
+
+#[async_trait]
+impl Indicator for SuperTrend {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let s = detail::<SuperTrendState>(state);
+
+        let Some(atr) = s.atr.next(bar, options.period) else {
+            return Vec::new();
+        };
+        let midpoint = (bar.high + bar.low) / 2.0;
+        let mut upper = midpoint + options.multiplier * atr;
+        let mut lower = midpoint - options.multiplier * atr;
+
+        let up = match &s.previous {
+            Some(previous) => {
+                if upper > previous.upper && previous.close <= previous.upper {
+                    upper = previous.upper;
+                }
+                if lower < previous.lower && previous.close >= previous.lower {
+                    lower = previous.lower;
+                }
+                if bar.close > previous.upper {
+                    true
+                } else if bar.close < previous.lower {
+                    false
+                } else {
+                    previous.up
+                }
+            }
+            None => true,
+        };
+        s.previous = Some(Bands {
+            upper,
+            lower,
+            close: bar.close,
+            up,
+        });
+
+        vec![
+            Output::main(if up { lower } else { upper }),
+            Output::named_int("direction", if up { 1 } else { -1 }),
+        ]
+    }
+}
@@ -0,0 +1,46 @@
+use super::{detail, ema, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// TRIX, the percentage change of a triple-smoothed EMA over `period` bars.
+///
+/// Each EMA is seeded with its first input like the `EMA` indicator, so TRIX
+/// is available from the second bar on and converges to TA-Lib `TRIX`, whose
+/// EMAs are seeded with an SMA, as the seeds decay.
+pub struct Trix;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrixState {
+    single: Option<f64>,
+    double: Option<f64>,
+    triple: Option<f64>,
+}
+
+#[async_trait]
+impl Indicator for Trix {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let period = options.period as usize;
+        let s = detail::<TrixState>(state);
+
+        let single = ema::next(s.single, period, bar.value);
+        let double = ema::next(s.double, period, single);
+        let triple = ema::next(s.triple, period, double);
+        s.single = Some(single);
+        s.double = Some(double);
+
+        match s.triple.replace(triple) {
+            Some(previous) if previous != 0.0 => {
+                vec![Output::main((triple / previous - 1.0) * 100.0)]
+            }
+            Some(_) => vec![Output::main(0.0)],
+            None => Vec::new(),
+        }
+    }
+}
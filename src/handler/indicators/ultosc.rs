@@ -0,0 +1,56 @@
+use super::{detail, window::Window, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Williams' Ultimate Oscillator over three periods, as TA-Lib `ULTOSC`.
+///
+/// The buying pressure `close - min(low, previous close)` relative to the true
+/// range is averaged over the short, medium and long period and the averages
+/// are weighted 4:2:1. Available from the `long_period + 1`th bar on.
+pub struct UltOsc;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UltOscState {
+    previous_close: Option<f64>,
+    buying_pressure: Window,
+    true_range: Window,
+}
+
+#[async_trait]
+impl Indicator for UltOsc {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let periods = options.periods();
+        let s = detail::<UltOscState>(state);
+
+        let Some(previous_close) = s.previous_close.replace(bar.close) else {
+            return Vec::new();
+        };
+        let true_low = bar.low.min(previous_close);
+        let true_high = bar.high.max(previous_close);
+        let long = periods[2] as usize;
+        s.buying_pressure.push(bar.close - true_low, long);
+        if !s.true_range.push(true_high - true_low, long) {
+            return Vec::new();
+        }
+
+        let average = |period: u32| {
+            let true_range = s.true_range.recent(period as usize).sum::<f64>();
+            if true_range > 0.0 {
+                s.buying_pressure.recent(period as usize).sum::<f64>() / true_range
+            } else {
+                0.0
+            }
+        };
+        let oscillator = 100.0
+            * (4.0 * average(periods[0]) + 2.0 * average(periods[1]) + average(periods[2]))
+            / 7.0;
+        vec![Output::main(oscillator)]
+    }
+}
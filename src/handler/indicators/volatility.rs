@@ -0,0 +1,117 @@
+use super::{detail, window::Window, Bar, Indicator, Output};
+use crate::handler::config::{IndicatorOptions, IndicatorState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::LN_2;
+
+/// Annualized realized volatility, the sample standard deviation of the last
+/// `period` one-bar log returns scaled by the square root of `annualization`.
+///
+/// Available from the `period + 1`th bar on, for a `period` of at least 2.
+pub struct Realized;
+
+/// Parkinson volatility from the high/low range of the last `period` bars,
+/// annualized like `Realized`. Available once `period` bars are known.
+pub struct Parkinson;
+
+/// Garman-Klass volatility from the open, high, low and close of the last
+/// `period` bars, annualized like `Realized`. Available once `period` bars are
+/// known.
+pub struct GarmanKlass;
+
+/// The per-bar terms of the window and the previous close.
+///
+/// Bars with a non-positive price have no logarithm and are skipped without
+/// touching the window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VolatilityState {
+    previous_close: Option<f64>,
+    terms: Window,
+}
+
+fn positive(prices: &[f64]) -> bool {
+    prices.iter().all(|&price| price > 0.0)
+}
+
+fn annualized(variance: f64, options: &IndicatorOptions) -> Output {
+    Output::main((variance.max(0.0) * options.annualization).sqrt())
+}
+
+#[async_trait]
+impl Indicator for Realized {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let period = options.period as usize;
+        let s = detail::<VolatilityState>(state);
+        if !positive(&[bar.value]) {
+            return Vec::new();
+        }
+        let Some(previous) = s.previous_close.replace(bar.value) else {
+            return Vec::new();
+        };
+        if !s.terms.push((bar.value / previous).ln(), period) || period < 2 {
+            return Vec::new();
+        }
+
+        let mean = s.terms.iter().sum::<f64>() / period as f64;
+        let variance =
+            s.terms.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (period - 1) as f64;
+        vec![annualized(variance, options)]
+    }
+}
+
+#[async_trait]
+impl Indicator for Parkinson {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let period = options.period as usize;
+        let s = detail::<VolatilityState>(state);
+        if !positive(&[bar.high, bar.low]) {
+            return Vec::new();
+        }
+        if !s.terms.push((bar.high / bar.low).ln().powi(2), period) {
+            return Vec::new();
+        }
+
+        let variance = s.terms.iter().sum::<f64>() / (4.0 * LN_2 * period as f64);
+        vec![annualized(variance, options)]
+    }
+}
+
+#[async_trait]
+impl Indicator for GarmanKlass {
+    async fn calculate(
+        &mut self,
+        state: &mut IndicatorState,
+        options: &IndicatorOptions,
+        bar: &Bar,
+    ) -> Vec<Output> {
+        state.count += 1;
+        let period = options.period as usize;
+        let s = detail::<VolatilityState>(state);
+        if !positive(&[bar.open, bar.high, bar.low, bar.close]) {
+            return Vec::new();
+        }
+        let range = (bar.high / bar.low).ln().powi(2);
+        let body = (bar.close / bar.open).ln().powi(2);
+        if !s
+            .terms
+            .push(0.5 * range - (2.0 * LN_2 - 1.0) * body, period)
+        {
+            return Vec::new();
+        }
+
+        let variance = s.terms.iter().sum::<f64>() / period as f64;
+        vec![annualized(variance, options)]
+    }
+}
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// The last values of a series, for indicators over a rolling window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Window {
+    values: VecDeque<f64>,
+}
+
+impl Window {
+    /// Appends a value, dropping the oldest ones beyond `len`, and returns whether the window is full.
+    pub fn push(&mut self, value: f64, len: usize) -> bool {
+        self.values.push_back(value);
+        while self.values.len() > len {
+            self.values.pop_front();
+        }
+        self.values.len() == len
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the values from the oldest to the newest.
+    pub fn iter(&self) -> impl Iterator<Item = f64> + '_ {
+        self.values.iter().copied()
+    }
+
+    /// Returns the newest `n` values from the oldest to the newest.
+    pub fn recent(&self, n: usize) -> impl Iterator<Item = f64> + '_ {
+        self.iter().skip(self.len().saturating_sub(n))
+    }
+
+    pub fn max(&self) -> f64 {
+        self.iter().fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    pub fn min(&self) -> f64 {
+        self.iter().fold(f64::INFINITY, f64::min)
+    }
+}
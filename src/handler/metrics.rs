@@ -0,0 +1,384 @@
+//! Runtime metrics shared by all handlers of the UDF.
+//!
+//! Metrics are updated by the accepter and the handlers and rendered in the
+//! Prometheus text exposition format, either directly through `Metrics::render`
+//! or over HTTP through `serve`. Handlers look up the counters of their task
+//! and indicator once in `init` and update them without locking.
+
+use async_std::{
+    io::{ReadExt, WriteExt},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    stream::StreamExt,
+    task,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    io,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tracing::{debug, info, warn};
+
+/// Reasons for which points are dropped instead of being emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DropReason {
+    /// The point has no tag named by `ticker_field`.
+    MissingTicker,
+    /// The point lacks one of the double fields the indicator reads.
+    MissingField,
+    /// The ticker and timestamp of the point were already processed.
+    Duplicate,
+    /// The point is older than the last point of its ticker and was not processed.
+    Late,
+    /// The point exceeded `max_batch_points` and was truncated.
+    Overflow,
+}
+
+impl DropReason {
+    /// All reasons, in the order they are rendered in.
+    pub const ALL: [DropReason; 5] = [
+        DropReason::MissingTicker,
+        DropReason::MissingField,
+        DropReason::Duplicate,
+        DropReason::Late,
+        DropReason::Overflow,
+    ];
+
+    /// Name of the reason, used in metric labels and in output fields.
+    pub fn label(self) -> &'static str {
+        match self {
+            DropReason::MissingTicker => "missing_ticker",
+            DropReason::MissingField => "missing_field",
+            DropReason::Duplicate => "duplicate",
+            DropReason::Late => "late",
+            DropReason::Overflow => "overflow",
+        }
+    }
+}
+
+/// Counters of a single Kapacitor task.
+#[derive(Debug, Default)]
+pub struct TaskMetrics {
+    /// Batches completed.
+    pub batches: AtomicU64,
+    /// Points received.
+    pub points_received: AtomicU64,
+    /// Points emitted with an indicator value.
+    pub points_emitted: AtomicU64,
+    /// Points dropped, indexed by `DropReason`.
+    points_dropped: [AtomicU64; DropReason::ALL.len()],
+    /// Tickers whose state was evicted.
+    pub evicted_series: AtomicU64,
+    /// Tickers currently tracked.
+    pub tracked_series: AtomicU64,
+    /// Handlers serving the task, only changed under the lock of `Metrics::tasks`.
+    /// The counters are dropped when the last one ends.
+    handlers: AtomicU64,
+}
+
+impl TaskMetrics {
+    /// Counts a dropped point.
+    pub fn point_dropped(&self, reason: DropReason) {
+        self.points_dropped[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of points dropped for the given reason.
+    pub fn points_dropped(&self, reason: DropReason) -> u64 {
+        self.points_dropped[reason as usize].load(Ordering::Relaxed)
+    }
+}
+
+/// Name, type, help text and accessor of a per-task metric.
+type TaskMetric = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&TaskMetrics) -> &AtomicU64,
+);
+
+/// Accumulated time spent computing an indicator.
+#[derive(Debug, Default)]
+pub struct Latency {
+    nanos: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Latency {
+    /// Records the time spent computing one value.
+    pub fn record(&self, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Metrics collected across all connections.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Connections accepted since startup.
+    pub connections_accepted: AtomicI64,
+    /// Handlers currently alive, one per connected Kapacitor task.
+    pub active_agents: AtomicI64,
+    /// Points currently buffered by all handlers, waiting for their EndBatch.
+    pub buffered_points: AtomicI64,
+    /// Approximate memory held by buffered points, measured as their protobuf encoded size.
+    pub buffered_bytes: AtomicI64,
+    tasks: Mutex<BTreeMap<String, Arc<TaskMetrics>>>,
+    latencies: Mutex<BTreeMap<String, Arc<Latency>>>,
+}
+
+impl Metrics {
+    /// Creates a new `Metrics` instance with all values at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a handler serving the given task and returns the task's counters.
+    pub fn task_started(&self, task_id: &str) -> Arc<TaskMetrics> {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        let task = tasks.entry(task_id.to_string()).or_default();
+        task.handlers.fetch_add(1, Ordering::Relaxed);
+        Arc::clone(task)
+    }
+
+    /// Unregisters a handler of the given task, dropping the task's counters
+    /// once no handler serves it anymore.
+    pub fn task_ended(&self, task_id: &str) {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(task) = tasks.get(task_id) {
+            if task.handlers.fetch_sub(1, Ordering::Relaxed) <= 1 {
+                tasks.remove(task_id);
+            }
+        }
+    }
+
+    /// Returns the compute latency of the given indicator.
+    pub fn latency(&self, indicator: &str) -> Arc<Latency> {
+        let mut latencies = self.latencies.lock().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(latencies.entry(indicator.to_string()).or_default())
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "indicator_udf_connections_accepted_total",
+            "counter",
+            "Connections accepted from Kapacitor.",
+        );
+        sample(
+            &mut out,
+            "indicator_udf_connections_accepted_total",
+            &[],
+            self.connections_accepted.load(Ordering::Relaxed),
+        );
+        header(
+            &mut out,
+            "indicator_udf_active_agents",
+            "gauge",
+            "Agents currently serving a connection.",
+        );
+        sample(
+            &mut out,
+            "indicator_udf_active_agents",
+            &[],
+            self.active_agents.load(Ordering::Relaxed),
+        );
+        header(
+            &mut out,
+            "indicator_udf_buffered_points",
+            "gauge",
+            "Points buffered until the end of their batch.",
+        );
+        sample(
+            &mut out,
+            "indicator_udf_buffered_points",
+            &[],
+            self.buffered_points.load(Ordering::Relaxed),
+        );
+        header(
+            &mut out,
+            "indicator_udf_buffered_bytes",
+            "gauge",
+            "Approximate size of the buffered points in bytes.",
+        );
+        sample(
+            &mut out,
+            "indicator_udf_buffered_bytes",
+            &[],
+            self.buffered_bytes.load(Ordering::Relaxed),
+        );
+
+        let tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let task_metrics: [TaskMetric; 5] = [
+            (
+                "indicator_udf_batches_total",
+                "counter",
+                "Batches processed.",
+                |m| &m.batches,
+            ),
+            (
+                "indicator_udf_points_received_total",
+                "counter",
+                "Points received.",
+                |m| &m.points_received,
+            ),
+            (
+                "indicator_udf_points_emitted_total",
+                "counter",
+                "Points emitted with an indicator value.",
+                |m| &m.points_emitted,
+            ),
+            (
+                "indicator_udf_evicted_series_total",
+                "counter",
+                "Tickers whose state was evicted.",
+                |m| &m.evicted_series,
+            ),
+            (
+                "indicator_udf_tracked_series",
+                "gauge",
+                "Tickers with indicator state.",
+                |m| &m.tracked_series,
+            ),
+        ];
+        for (name, kind, help, value) in task_metrics {
+            header(&mut out, name, kind, help);
+            for (task, m) in &tasks {
+                sample(
+                    &mut out,
+                    name,
+                    &[("task", task)],
+                    value(m).load(Ordering::Relaxed),
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "indicator_udf_points_dropped_total",
+            "counter",
+            "Points dropped instead of emitted, by reason.",
+        );
+        for (task, m) in &tasks {
+            for reason in DropReason::ALL {
+                let count = m.points_dropped(reason);
+                if count > 0 {
+                    sample(
+                        &mut out,
+                        "indicator_udf_points_dropped_total",
+                        &[("task", task), ("reason", reason.label())],
+                        count,
+                    );
+                }
+            }
+        }
+
+        let latencies = self
+            .latencies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        header(
+            &mut out,
+            "indicator_udf_compute_seconds",
+            "summary",
+            "Time spent computing indicator values.",
+        );
+        for (indicator, latency) in &latencies {
+            sample(
+                &mut out,
+                "indicator_udf_compute_seconds_sum",
+                &[("indicator", indicator)],
+                latency.nanos.load(Ordering::Relaxed) as f64 / 1e9,
+            );
+            sample(
+                &mut out,
+                "indicator_udf_compute_seconds_count",
+                &[("indicator", indicator)],
+                latency.count.load(Ordering::Relaxed),
+            );
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serves the metrics over HTTP on the given address until the listener fails.
+///
+/// Every request is answered with the current metrics, except for paths other
+/// than `/metrics` and `/`, which get a 404.
+pub async fn serve(addr: impl ToSocketAddrs, metrics: Arc<Metrics>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving metrics on {}", listener.local_addr()?);
+
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        match stream {
+            Ok(stream) => {
+                let metrics = Arc::clone(&metrics);
+                task::spawn(async move {
+                    if let Err(e) = respond(stream, &metrics).await {
+                        debug!("Failed to answer metrics request: {}", e);
+                    }
+                });
+            }
+            Err(e) => warn!("Failed to accept metrics connection: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    // Only the request line matters, headers and body are ignored
+    let mut buf = vec![0u8; 1024];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let response = match path {
+        "/" | "/metrics" => {
+            let body = metrics.render();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        }
+        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.flush().await
+}
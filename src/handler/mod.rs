@@ -2,3 +2,8 @@ pub mod accepter;
 pub mod config;
 pub mod indicator_handler;
 pub mod indicators;
+pub mod metrics;
+pub mod point_trace;
+pub mod signal;
+pub mod snapshot;
+pub mod state_store;
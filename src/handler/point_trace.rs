@@ -0,0 +1,55 @@
+//! Selective point-level tracing.
+//!
+//! Logging every point is far too verbose for production, so per-point logs are
+//! emitted at trace level. A `PointTrace` selects a few points, those of chosen
+//! tickers and a sample of all others, that are logged at info level under the
+//! `point_trace` target instead.
+
+use std::{
+    collections::HashSet,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The log target of traced points, usable in filter directives.
+pub const TARGET: &str = "point_trace";
+
+/// Selects the points whose processing is logged under `TARGET`.
+#[derive(Debug, Default)]
+pub struct PointTrace {
+    tickers: HashSet<String>,
+    sample_rate: u64,
+    seen: AtomicU64,
+}
+
+impl PointTrace {
+    /// Creates a new `PointTrace` that selects no points.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Selects all points of the given tickers.
+    pub fn with_tickers(mut self, tickers: impl IntoIterator<Item = String>) -> Self {
+        self.tickers.extend(tickers);
+        self
+    }
+
+    /// Selects every `sample_rate`th point of any ticker, 0 disables sampling.
+    pub fn with_sample_rate(mut self, sample_rate: u64) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Whether any point can be selected at all.
+    pub fn is_enabled(&self) -> bool {
+        !self.tickers.is_empty() || self.sample_rate > 0
+    }
+
+    /// Decides whether the next point of the given ticker is traced.
+    ///
+    /// Every call counts towards the sample, whether the ticker is selected or not.
+    pub fn select(&self, ticker: &str) -> bool {
+        let sampled = self.sample_rate > 0
+            && self.seen.fetch_add(1, Ordering::Relaxed) % self.sample_rate == 0;
+        sampled || self.tickers.contains(ticker)
+    }
+}
@@ -0,0 +1,57 @@
+use super::config::IndicatorOptions;
+use kapacitor_udf::proto::Point;
+
+/// Direction in which the watched field crossed its reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cross {
+    Up,
+    Down,
+}
+
+impl Cross {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Cross::Up => "cross_up",
+            Cross::Down => "cross_down",
+        }
+    }
+}
+
+/// Compares `signal_on` of a point with `signal_cross`, or with
+/// `signal_threshold` if no field is given, and returns the crossing since the
+/// previous point of the ticker.
+///
+/// `last_difference` holds the last non-zero difference of the ticker, so a
+/// field touching its reference signals only once it moves to the other side.
+/// Points lacking one of the fields leave it unchanged.
+pub fn detect(
+    options: &IndicatorOptions,
+    point: &Point,
+    last_difference: &mut Option<f64>,
+) -> Option<Cross> {
+    let value = field(point, &options.signal_on)?;
+    let reference = if options.signal_cross.is_empty() {
+        options.signal_threshold
+    } else {
+        field(point, &options.signal_cross)?
+    };
+    let difference = value - reference;
+    if difference == 0.0 || difference.is_nan() {
+        return None;
+    }
+
+    match last_difference.replace(difference) {
+        Some(last) if last < 0.0 && difference > 0.0 => Some(Cross::Up),
+        Some(last) if last > 0.0 && difference < 0.0 => Some(Cross::Down),
+        _ => None,
+    }
+}
+
+/// Reads a double or integer field of a point.
+fn field(point: &Point, name: &str) -> Option<f64> {
+    point
+        .fields_double
+        .get(name)
+        .copied()
+        .or_else(|| point.fields_int.get(name).map(|&v| v as f64))
+}
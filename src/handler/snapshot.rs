@@ -0,0 +1,185 @@
+//! Versioned snapshot format for the state handed to Kapacitor.
+//!
+//! Every snapshot is wrapped in an envelope recording the format version, the
+//! indicator type and hashes of the options the state was computed with. JSON
+//! snapshots are stored as plain JSON, binary snapshots as MessagePack prefixed
+//! with `BINARY_MAGIC`. Snapshots written before the envelope existed, a bare
+//! JSON object holding only `states`, are migrated on restore.
+
+use crate::handler::config::{IndicatorOptions, IndicatorState, IndicatorType, SnapshotFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// The snapshot format version written by this build.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Prefix identifying binary snapshots. JSON snapshots always start with `{`.
+const BINARY_MAGIC: &[u8] = b"KMIB";
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("Failed to encode snapshot: {0}")]
+    Encode(String),
+    #[error("Failed to decode snapshot: {0}")]
+    Decode(String),
+    #[error("Unsupported snapshot version {0}, this build reads up to version {SNAPSHOT_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("Snapshot was taken for indicator type {snapshot:?}, but the task runs {running:?}")]
+    IndicatorMismatch {
+        snapshot: IndicatorType,
+        running: IndicatorType,
+    },
+    #[error("Snapshot was taken with different indicator options (options hash {snapshot:016x}, task uses {running:016x})")]
+    OptionsMismatch { snapshot: u64, running: u64 },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotEnvelope<S> {
+    version: u32,
+    indicator_type: IndicatorType,
+    options_hash: u64,
+    /// Hash of the signal options, missing from snapshots taken before it was recorded.
+    #[serde(default)]
+    signal_hash: u64,
+    states: S,
+}
+
+/// Just the version of an envelope, read before committing to a full decode.
+#[derive(Debug, Deserialize)]
+struct SnapshotHeader {
+    version: u32,
+}
+
+/// The unversioned layout used before the envelope was introduced.
+#[derive(Debug, Deserialize)]
+struct LegacySnapshot {
+    states: HashMap<String, IndicatorState>,
+}
+
+fn envelope<S>(options: &IndicatorOptions, states: S) -> SnapshotEnvelope<S> {
+    SnapshotEnvelope {
+        version: SNAPSHOT_VERSION,
+        indicator_type: options.indicator_type.clone(),
+        options_hash: options.state_hash(),
+        signal_hash: options.signal_hash(),
+        states,
+    }
+}
+
+/// Encodes the per-ticker states into a snapshot using the format selected in the options.
+pub fn encode(
+    options: &IndicatorOptions,
+    states: &HashMap<String, IndicatorState>,
+) -> Result<Vec<u8>, SnapshotError> {
+    let envelope = envelope(options, states);
+
+    match options.snapshot_format {
+        SnapshotFormat::Json => {
+            serde_json::to_vec(&envelope).map_err(|e| SnapshotError::Encode(e.to_string()))
+        }
+        SnapshotFormat::Binary => {
+            let mut bytes = BINARY_MAGIC.to_vec();
+            let body = rmp_serde::to_vec_named(&envelope)
+                .map_err(|e| SnapshotError::Encode(e.to_string()))?;
+            bytes.extend_from_slice(&body);
+            Ok(bytes)
+        }
+    }
+}
+
+/// Encodes the state of a single ticker as a JSON snapshot, the format of the state store.
+pub fn encode_ticker(
+    options: &IndicatorOptions,
+    ticker: &str,
+    state: &IndicatorState,
+) -> Result<Vec<u8>, SnapshotError> {
+    let envelope = envelope(options, HashMap::from([(ticker, state)]));
+    serde_json::to_vec(&envelope).map_err(|e| SnapshotError::Encode(e.to_string()))
+}
+
+/// Decodes a snapshot in any supported format and version.
+///
+/// The snapshot is rejected if it was taken for a different indicator type or
+/// with options that give the state a different meaning than `options`. If
+/// only the signal options differ, the states are kept without the side of
+/// their last signal comparison.
+pub fn decode(
+    bytes: &[u8],
+    options: &IndicatorOptions,
+) -> Result<HashMap<String, IndicatorState>, SnapshotError> {
+    let envelope = if let Some(body) = bytes.strip_prefix(BINARY_MAGIC) {
+        debug!("Decoding binary snapshot");
+        let header: SnapshotHeader =
+            rmp_serde::from_slice(body).map_err(|e| SnapshotError::Decode(e.to_string()))?;
+        check_version(header.version)?;
+        rmp_serde::from_slice(body).map_err(|e| SnapshotError::Decode(e.to_string()))?
+    } else {
+        debug!("Decoding JSON snapshot");
+        let value: serde_json::Value =
+            serde_json::from_slice(bytes).map_err(|e| SnapshotError::Decode(e.to_string()))?;
+        if value.get("version").is_none() {
+            return migrate_legacy(value);
+        }
+        let header: SnapshotHeader = serde_json::from_value(value.clone())
+            .map_err(|e| SnapshotError::Decode(e.to_string()))?;
+        check_version(header.version)?;
+        serde_json::from_value(value).map_err(|e| SnapshotError::Decode(e.to_string()))?
+    };
+
+    verify(envelope, options)
+}
+
+fn check_version(version: u32) -> Result<(), SnapshotError> {
+    if version == 0 || version > SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+fn verify(
+    envelope: SnapshotEnvelope<HashMap<String, IndicatorState>>,
+    options: &IndicatorOptions,
+) -> Result<HashMap<String, IndicatorState>, SnapshotError> {
+    if envelope.indicator_type != options.indicator_type {
+        return Err(SnapshotError::IndicatorMismatch {
+            snapshot: envelope.indicator_type,
+            running: options.indicator_type.clone(),
+        });
+    }
+
+    let running = options.state_hash();
+    if envelope.options_hash != running {
+        return Err(SnapshotError::OptionsMismatch {
+            snapshot: envelope.options_hash,
+            running,
+        });
+    }
+
+    let mut states = envelope.states;
+    if envelope.signal_hash != options.signal_hash() {
+        debug!("Snapshot was taken with different signal options, forgetting the signal sides");
+        for state in states.values_mut() {
+            state.signal_difference = None;
+        }
+    }
+
+    Ok(states)
+}
+
+/// Migrates a pre-envelope JSON snapshot.
+///
+/// These snapshots carry no record of the options they were taken with, so they
+/// are accepted as-is.
+fn migrate_legacy(
+    value: serde_json::Value,
+) -> Result<HashMap<String, IndicatorState>, SnapshotError> {
+    let legacy: LegacySnapshot =
+        serde_json::from_value(value).map_err(|e| SnapshotError::Decode(e.to_string()))?;
+    warn!(
+        "Migrating unversioned snapshot with {} ticker states, options cannot be verified",
+        legacy.states.len()
+    );
+    Ok(legacy.states)
+}
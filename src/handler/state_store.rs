@@ -0,0 +1,222 @@
+//! Durable storage for per-ticker indicator state.
+//!
+//! Kapacitor only hands state back to the UDF through `restore` when it restores
+//! a task from its own snapshot. A `StateStore` keeps the same state on the UDF
+//! side so that restarting the UDF process does not reset every indicator to its
+//! seed value.
+//!
+//! States are stored as encoded by `snapshot::encode_ticker`, so they record
+//! the indicator type and options they were computed with, and the handler can
+//! discard those that no longer fit the task.
+
+use async_std::{fs, io::WriteExt, path::PathBuf, stream::StreamExt};
+use async_trait::async_trait;
+use std::{collections::HashMap, fmt::Debug, io};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+#[derive(Debug, Error)]
+pub enum StateStoreError {
+    #[error("I/O error on {0}: {1}")]
+    Io(String, #[source] io::Error),
+}
+
+/// A pluggable backend for persisting encoded indicator state keyed by task ID and ticker.
+#[async_trait]
+pub trait StateStore: Send + Sync + Debug {
+    /// Loads all encoded ticker states stored for the given task.
+    ///
+    /// Returns an empty map if nothing has been stored for the task yet.
+    async fn load(&self, task_id: &str) -> Result<HashMap<String, Vec<u8>>, StateStoreError>;
+
+    /// Stores the encoded state of a single ticker of the given task, replacing any previous value.
+    async fn save(&self, task_id: &str, ticker: &str, state: &[u8]) -> Result<(), StateStoreError>;
+
+    /// Stores the encoded states of several tickers of the given task at once.
+    ///
+    /// The handler flushes all tickers touched by a batch through this method,
+    /// so backends should override it when writing many values together is
+    /// cheaper than writing them one by one. On error, any subset of the states
+    /// may have been stored.
+    async fn save_many(
+        &self,
+        task_id: &str,
+        states: &[(String, Vec<u8>)],
+    ) -> Result<(), StateStoreError> {
+        for (ticker, state) in states {
+            self.save(task_id, ticker, state).await?;
+        }
+        Ok(())
+    }
+
+    /// Removes the stored state of a single ticker of the given task, if any.
+    async fn remove(&self, task_id: &str, ticker: &str) -> Result<(), StateStoreError>;
+}
+
+/// A `StateStore` that keeps one JSON file per ticker below a directory per task.
+///
+/// The layout is `<root>/<task id>/<ticker>.json`, with both components
+/// percent-encoded so arbitrary task IDs and tickers map to valid file names.
+/// Files are written to a temporary file first, synced and renamed into place,
+/// so a crash during a flush never leaves a half-written state behind. A flush
+/// of several tickers writes all temporary files before syncing them, and
+/// syncs the directory once after renaming them.
+#[derive(Debug, Clone)]
+pub struct FileStateStore {
+    root: PathBuf,
+}
+
+impl FileStateStore {
+    /// Creates a new `FileStateStore` rooted at the given directory.
+    ///
+    /// The directory is created lazily on the first write.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileStateStore { root: root.into() }
+    }
+
+    fn task_dir(&self, task_id: &str) -> PathBuf {
+        self.root.join(encode_key(task_id))
+    }
+}
+
+#[async_trait]
+impl StateStore for FileStateStore {
+    async fn load(&self, task_id: &str) -> Result<HashMap<String, Vec<u8>>, StateStoreError> {
+        let dir = self.task_dir(task_id);
+        let mut states = HashMap::new();
+
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                debug!("No stored state for task {} at {:?}", task_id, dir);
+                return Ok(states);
+            }
+            Err(e) => return Err(StateStoreError::Io(dir.display().to_string(), e)),
+        };
+
+        while let Some(entry) = entries.next().await {
+            let entry = entry.map_err(|e| StateStoreError::Io(dir.display().to_string(), e))?;
+            let path = entry.path();
+            let ticker = match path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(decode_key)
+            {
+                Some(ticker) => ticker,
+                None => {
+                    debug!("Skipping unrelated file in state directory: {:?}", path);
+                    continue;
+                }
+            };
+
+            match fs::read(&path).await {
+                Ok(bytes) => {
+                    states.insert(ticker, bytes);
+                }
+                Err(e) => warn!("Ignoring unreadable state file {:?}: {}", path, e),
+            }
+        }
+
+        debug!("Loaded {} ticker states for task {}", states.len(), task_id);
+        Ok(states)
+    }
+
+    async fn save(&self, task_id: &str, ticker: &str, state: &[u8]) -> Result<(), StateStoreError> {
+        self.save_many(task_id, &[(ticker.to_string(), state.to_vec())])
+            .await
+    }
+
+    async fn save_many(
+        &self,
+        task_id: &str,
+        states: &[(String, Vec<u8>)],
+    ) -> Result<(), StateStoreError> {
+        if states.is_empty() {
+            return Ok(());
+        }
+        let dir = self.task_dir(task_id);
+        let dir_error = |e| StateStoreError::Io(dir.display().to_string(), e);
+        fs::create_dir_all(&dir).await.map_err(dir_error)?;
+
+        let mut written = Vec::with_capacity(states.len());
+        for (ticker, state) in states {
+            let path = dir.join(format!("{}.json", encode_key(ticker)));
+            let tmp_path = dir.join(format!("{}.json.tmp", encode_key(ticker)));
+            let tmp_error = |e| StateStoreError::Io(tmp_path.display().to_string(), e);
+            let mut file = fs::File::create(&tmp_path).await.map_err(tmp_error)?;
+            file.write_all(state).await.map_err(tmp_error)?;
+            written.push((file, tmp_path, path));
+        }
+
+        // Without syncing, the renames may reach the disk before the data
+        for (file, tmp_path, _) in &written {
+            file.sync_all()
+                .await
+                .map_err(|e| StateStoreError::Io(tmp_path.display().to_string(), e))?;
+        }
+        for (file, tmp_path, path) in written {
+            drop(file);
+            fs::rename(&tmp_path, &path)
+                .await
+                .map_err(|e| StateStoreError::Io(path.display().to_string(), e))?;
+        }
+        sync_dir(&dir).await.map_err(dir_error)?;
+
+        debug!("Saved {} ticker states for task {}", states.len(), task_id);
+        Ok(())
+    }
+
+    async fn remove(&self, task_id: &str, ticker: &str) -> Result<(), StateStoreError> {
+        let path = self
+            .task_dir(task_id)
+            .join(format!("{}.json", encode_key(ticker)));
+        match fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(StateStoreError::Io(path.display().to_string(), e)),
+        }
+    }
+}
+
+/// Syncs a directory, making the renames within it durable.
+#[cfg(unix)]
+async fn sync_dir(dir: &PathBuf) -> io::Result<()> {
+    fs::File::open(dir).await?.sync_all().await
+}
+
+/// Directories cannot be opened for syncing outside of Unix, where renames are
+/// left to the file system to make durable.
+#[cfg(not(unix))]
+async fn sync_dir(_dir: &PathBuf) -> io::Result<()> {
+    Ok(())
+}
+
+/// Percent-encodes everything except ASCII alphanumerics and `-`.
+fn encode_key(key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Reverses `encode_key`, returning `None` for names it could not have produced.
+fn decode_key(encoded: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
@@ -0,0 +1,221 @@
+//! In-process harness for driving an `IndicatorHandler` without Kapacitor.
+//!
+//! The harness owns a handler together with the receiving end of its response
+//! channel, so tests can call the `Handler` methods directly and inspect what
+//! would have been written to the socket, grouped into batches.
+
+use crate::handler::{
+    config::{parse_option, IndicatorOptions},
+    indicator_handler::IndicatorHandler,
+};
+use async_std::{
+    channel::{self, Receiver},
+    io,
+    sync::{Arc, Mutex},
+};
+use kapacitor_udf::{
+    proto::{
+        response, BeginBatch, EndBatch, InfoResponse, InitRequest, InitResponse, Point, Response,
+        RestoreRequest, RestoreResponse,
+    },
+    traits::Handler,
+};
+
+/// A batch as forwarded by the handler.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchOutput {
+    pub begin: BeginBatch,
+    pub points: Vec<Point>,
+    /// `None` if the batch had not ended yet when the responses were taken.
+    pub end: Option<EndBatch>,
+}
+
+impl BatchOutput {
+    /// Returns the values of a double field of all points, `None` for points without it.
+    pub fn values(&self, field: &str) -> Vec<Option<f64>> {
+        self.points
+            .iter()
+            .map(|p| p.fields_double.get(field).copied())
+            .collect()
+    }
+
+    /// Returns the values of an integer field of all points, `None` for points without it.
+    pub fn int_values(&self, field: &str) -> Vec<Option<i64>> {
+        self.points
+            .iter()
+            .map(|p| p.fields_int.get(field).copied())
+            .collect()
+    }
+}
+
+/// Drives an `IndicatorHandler` and captures its responses.
+pub struct HandlerHarness {
+    handler: IndicatorHandler,
+    responses: Receiver<Response>,
+}
+
+impl HandlerHarness {
+    /// Creates a new harness around a handler with default options.
+    pub async fn new() -> Self {
+        let (sender, responses) = channel::unbounded();
+        let handler =
+            IndicatorHandler::new(Arc::new(Mutex::new(sender)), IndicatorOptions::default()).await;
+        HandlerHarness { handler, responses }
+    }
+
+    /// Applies builder methods to the handler, e.g. to attach a state store before `init`.
+    pub fn map_handler(mut self, f: impl FnOnce(IndicatorHandler) -> IndicatorHandler) -> Self {
+        self.handler = f(self.handler);
+        self
+    }
+
+    /// Returns the handler, e.g. to call `Handler` methods the harness does not wrap.
+    pub fn handler(&mut self) -> &mut IndicatorHandler {
+        &mut self.handler
+    }
+
+    /// Returns the handler's info response.
+    pub async fn info(&self) -> io::Result<InfoResponse> {
+        self.handler.info().await
+    }
+
+    /// Initializes the handler with options given as text, as in `-o name=value`.
+    ///
+    /// Options that cannot be parsed fail with `InvalidInput` instead of
+    /// reaching the handler.
+    pub async fn init(&mut self, options: &[(&str, &str)]) -> io::Result<InitResponse> {
+        let options = options
+            .iter()
+            .map(|(name, value)| parse_option(name, value))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.init_request(&InitRequest {
+            options,
+            task_id: "harness".to_string(),
+            node_id: "harness".to_string(),
+        })
+        .await
+    }
+
+    /// Initializes the handler with a raw request.
+    pub async fn init_request(&mut self, request: &InitRequest) -> io::Result<InitResponse> {
+        self.handler.init(request).await
+    }
+
+    /// Starts a batch.
+    pub async fn begin_batch(&mut self, begin: &BeginBatch) -> io::Result<()> {
+        self.handler.begin_batch(begin).await
+    }
+
+    /// Sends a single point of the current batch.
+    pub async fn point(&mut self, point: &Point) -> io::Result<()> {
+        self.handler.point(point).await
+    }
+
+    /// Ends the current batch, which makes the handler forward its output.
+    pub async fn end_batch(&mut self, end: &EndBatch) -> io::Result<()> {
+        self.handler.end_batch(end).await
+    }
+
+    /// Sends the points as one batch and returns the batches forwarded in response.
+    ///
+    /// The BeginBatch is named after the first point and the EndBatch carries
+    /// the time of the last point.
+    pub async fn batch(&mut self, points: &[Point]) -> io::Result<Vec<BatchOutput>> {
+        let name = points.first().map(|p| p.name.clone()).unwrap_or_default();
+        self.begin_batch(&BeginBatch {
+            name: name.clone(),
+            size: points.len() as i64,
+            ..Default::default()
+        })
+        .await?;
+        for point in points {
+            self.point(point).await?;
+        }
+        self.end_batch(&EndBatch {
+            name,
+            tmax: points.last().map(|p| p.time).unwrap_or_default(),
+            ..Default::default()
+        })
+        .await?;
+        Ok(self.take_batches())
+    }
+
+    /// Takes a snapshot of the handler's state.
+    pub async fn snapshot(&self) -> io::Result<Vec<u8>> {
+        Ok(self.handler.snapshot().await?.snapshot)
+    }
+
+    /// Restores the handler's state from a snapshot.
+    pub async fn restore(&mut self, snapshot: &[u8]) -> io::Result<RestoreResponse> {
+        self.handler
+            .restore(&RestoreRequest {
+                snapshot: snapshot.to_vec(),
+            })
+            .await
+    }
+
+    /// Takes all responses sent by the handler so far.
+    pub fn take_responses(&mut self) -> Vec<Response> {
+        let mut responses = Vec::new();
+        while let Ok(response) = self.responses.try_recv() {
+            responses.push(response);
+        }
+        responses
+    }
+
+    /// Takes all responses sent by the handler so far, grouped into batches.
+    ///
+    /// Responses other than batch messages, and points sent outside of a batch,
+    /// are discarded.
+    pub fn take_batches(&mut self) -> Vec<BatchOutput> {
+        let mut batches: Vec<BatchOutput> = Vec::new();
+        for response in self.take_responses() {
+            match response.message {
+                Some(response::Message::Begin(begin)) => batches.push(BatchOutput {
+                    begin,
+                    ..Default::default()
+                }),
+                Some(response::Message::Point(point)) => {
+                    if let Some(batch) = batches.last_mut().filter(|b| b.end.is_none()) {
+                        batch.points.push(point);
+                    }
+                }
+                Some(response::Message::End(end)) => {
+                    if let Some(batch) = batches.last_mut().filter(|b| b.end.is_none()) {
+                        batch.end = Some(end);
+                    }
+                }
+                _ => {}
+            }
+        }
+        batches
+    }
+}
+
+/// Builds a point of the given ticker with a single double field, as the default options expect.
+pub fn point(time: i64, ticker: &str, value: f64) -> Point {
+    Point {
+        time,
+        name: "harness".to_string(),
+        tags: [("ticker".to_string(), ticker.to_string())].into(),
+        fields_double: [("value".to_string(), value)].into(),
+        ..Default::default()
+    }
+}
+
+/// Builds a point of the given ticker with the high, low and close fields the default options expect.
+pub fn bar(time: i64, ticker: &str, high: f64, low: f64, close: f64) -> Point {
+    Point {
+        time,
+        name: "harness".to_string(),
+        tags: [("ticker".to_string(), ticker.to_string())].into(),
+        fields_double: [
+            ("high".to_string(), high),
+            ("low".to_string(), low),
+            ("close".to_string(), close),
+        ]
+        .into(),
+        ..Default::default()
+    }
+}
@@ -1 +1,4 @@
+pub mod backtest;
 pub mod handler;
+pub mod harness;
+pub mod replay;
@@ -5,34 +5,149 @@
 //! and handles graceful shutdown on termination signals.
 
 use async_std::{fs, io, main, os::unix::net::UnixListener, stream::StreamExt, sync::Arc, task};
-use clap::Parser;
-use kapacitor_multi_indicator_batch_udf::handler::accepter::Accepter;
+use clap::{Parser, Subcommand, ValueEnum};
+use kapacitor_multi_indicator_batch_udf::{
+    backtest::{self, Backtest, Format},
+    handler::{
+        accepter::Accepter,
+        config::parse_option,
+        metrics::{self, Metrics},
+        point_trace::{self, PointTrace},
+        state_store::FileStateStore,
+    },
+    replay::Session,
+};
+use kapacitor_udf::proto::Option as ProtoOption;
 use kapacitor_udf::socket_server::SocketServer;
 use libc::{SIGINT, SIGTERM};
 use signal_hook_async_std::Signals;
-use std::path::PathBuf;
-use tracing::{debug, error, info, warn, Level};
-use tracing_subscriber::FmtSubscriber;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+use tracing::{debug, error, info, level_filters::LevelFilter, warn};
+use tracing_subscriber::{filter::Directive, fmt::writer::BoxMakeWriter, EnvFilter, FmtSubscriber};
+
+/// Output format of the log.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LogFormat {
+    /// Human readable lines.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
 
 /// Command-line arguments for the UDF server.
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// Path to the Unix socket file.
     #[clap(short, long, default_value = "/tmp/indicator-batch.sock")]
     socket: PathBuf,
+
+    /// Directory to persist per-ticker indicator state in, independent of Kapacitor snapshots.
+    #[clap(long)]
+    state_dir: Option<PathBuf>,
+
+    /// Address to serve Prometheus metrics on, e.g. `127.0.0.1:9464`.
+    #[clap(long)]
+    metrics_addr: Option<String>,
+
+    /// Directory to record the requests and responses of every connection to, for `replay`.
+    #[clap(long)]
+    record_dir: Option<PathBuf>,
+
+    /// Default log level, overridden per module by `RUST_LOG` and `--log-filter`.
+    #[clap(long, global = true, default_value = "info")]
+    log_level: LevelFilter,
+
+    /// Log output format.
+    #[clap(long, global = true, value_enum, default_value = "text")]
+    log_format: LogFormat,
+
+    /// Additional comma-separated filter directives, e.g. `kapacitor_udf=warn`.
+    #[clap(long, global = true)]
+    log_filter: Option<String>,
+
+    /// Log the processing of every point of this ticker at info level, may be repeated.
+    #[clap(long = "trace-ticker")]
+    trace_tickers: Vec<String>,
+
+    /// Log the processing of every Nth point of any ticker at info level.
+    #[clap(long, default_value = "0")]
+    trace_sample_rate: u64,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run an indicator over line protocol or CSV data instead of serving Kapacitor.
+    Backtest(BacktestArgs),
+    /// Replay a recorded session against the handler and report differing responses.
+    Replay {
+        /// The `.requests` or `.responses` file of the session, or their path without extension.
+        session: PathBuf,
+    },
+}
+
+/// Command-line arguments for the backtest mode.
+#[derive(clap::Args, Debug)]
+struct BacktestArgs {
+    /// Input file, `-` or none to read from stdin.
+    input: Option<PathBuf>,
+
+    /// Output file, stdout if not given.
+    #[clap(long)]
+    output: Option<PathBuf>,
+
+    /// UDF option as `name=value`, may be repeated, e.g. `-o type=SMA -o period=20`.
+    #[clap(short = 'o', long = "option", value_parser = parse_option_arg)]
+    options: Vec<ProtoOption>,
+
+    /// Input format.
+    #[clap(short, long, value_enum, default_value = "lp")]
+    format: Format,
+
+    /// Output format, the input format if not given.
+    #[clap(long, value_enum)]
+    output_format: Option<Format>,
+
+    /// Measurement name of the points read from CSV.
+    #[clap(long, default_value = "backtest")]
+    measurement: String,
+
+    /// Maximum number of points per batch, 0 to process each ticker in a single batch.
+    #[clap(long, default_value = "0")]
+    batch_size: usize,
+}
+
+fn parse_option_arg(arg: &str) -> Result<ProtoOption, String> {
+    let (name, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected name=value, got {:?}", arg))?;
+    parse_option(name.trim(), value.trim()).map_err(|e| e.to_string())
 }
 
 #[main]
 async fn main() -> io::Result<()> {
-    // Initialize the tracing subscriber with a maximum log level
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::DEBUG)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber).expect("Failed to set tracing subscriber");
-
     // Parse command-line arguments
     let args = Args::parse();
+
+    let point_trace = PointTrace::new()
+        .with_tickers(args.trace_tickers.iter().cloned())
+        .with_sample_rate(args.trace_sample_rate);
+
+    init_tracing(&args, point_trace.is_enabled());
+
+    match args.command {
+        Some(Command::Backtest(backtest_args)) => return run_backtest(backtest_args).await,
+        Some(Command::Replay { session }) => return run_replay(session).await,
+        None => {}
+    }
+
     info!("Main() started");
 
     // Define the path for the Unix socket
@@ -52,8 +167,40 @@ async fn main() -> io::Result<()> {
     // Create a new Unix socket listener
     let listener = UnixListener::bind(&socket_path).await?;
 
+    // Create the accepter, persisting state to disk if requested
+    let mut accepter = Accepter::new();
+    if let Some(state_dir) = args.state_dir {
+        info!("Persisting indicator state to {}", state_dir.display());
+        accepter = accepter.with_state_store(Arc::new(FileStateStore::new(state_dir)));
+    }
+
+    if let Some(record_dir) = args.record_dir {
+        fs::create_dir_all(&record_dir).await?;
+        info!("Recording connections to {}", record_dir.display());
+        accepter = accepter.with_record_dir(record_dir);
+    }
+
+    if point_trace.is_enabled() {
+        info!(
+            "Tracing points of tickers {:?} and every {}th point",
+            args.trace_tickers, args.trace_sample_rate
+        );
+        accepter = accepter.with_point_trace(Arc::new(point_trace));
+    }
+
+    // Collect and serve metrics if requested
+    if let Some(metrics_addr) = args.metrics_addr {
+        let metrics = Arc::new(Metrics::new());
+        accepter = accepter.with_metrics(Arc::clone(&metrics));
+        task::spawn(async move {
+            if let Err(e) = metrics::serve(metrics_addr.as_str(), metrics).await {
+                error!("Metrics endpoint error: {}", e);
+            }
+        });
+    }
+
     // Create a new server instance
-    let server = Arc::new(SocketServer::new(listener, Accepter::new()));
+    let server = Arc::new(SocketServer::new(listener, accepter));
     let server_clone = Arc::clone(&server);
 
     // Task for serving requests
@@ -96,3 +243,109 @@ async fn main() -> io::Result<()> {
 
     Ok(())
 }
+
+/// Installs the global tracing subscriber configured by the command-line arguments.
+///
+/// Directives are applied in order of increasing precedence: `--log-level`,
+/// `RUST_LOG` and `--log-filter`. When point tracing is enabled, the
+/// `point_trace` target is logged at info level regardless of the other directives.
+fn init_tracing(args: &Args, point_trace: bool) {
+    let mut filter = EnvFilter::builder()
+        .with_default_directive(args.log_level.into())
+        .from_env_lossy();
+    for directive in args.log_filter.iter().flat_map(|f| f.split(',')) {
+        match directive.trim().parse::<Directive>() {
+            Ok(directive) => filter = filter.add_directive(directive),
+            Err(e) => eprintln!(
+                "Ignoring invalid log filter directive {:?}: {}",
+                directive, e
+            ),
+        }
+    }
+    if point_trace {
+        filter = filter.add_directive(
+            format!("{}=info", point_trace::TARGET)
+                .parse()
+                .expect("Point trace directive is valid"),
+        );
+    }
+
+    // Backtests write their results to stdout, so logs go to stderr
+    let writer = match args.command {
+        Some(_) => BoxMakeWriter::new(std::io::stderr),
+        None => BoxMakeWriter::new(std::io::stdout),
+    };
+
+    let builder = FmtSubscriber::builder()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    let result = match args.log_format {
+        LogFormat::Text => tracing::subscriber::set_global_default(builder.finish()),
+        LogFormat::Json => tracing::subscriber::set_global_default(builder.json().finish()),
+    };
+    result.expect("Failed to set tracing subscriber");
+}
+
+/// Runs a backtest and writes the enriched points.
+async fn run_backtest(args: BacktestArgs) -> io::Result<()> {
+    let to_io = |e: backtest::BacktestError| io::Error::new(io::ErrorKind::Other, e);
+
+    let backtest = Backtest::new(args.options)
+        .map_err(to_io)?
+        .with_batch_size(args.batch_size);
+    let ticker_field = &backtest.options().ticker_field;
+
+    let points = match args.input.filter(|path| path.as_os_str() != "-") {
+        Some(path) => backtest::read_points(
+            args.format,
+            BufReader::new(File::open(path)?),
+            &args.measurement,
+            ticker_field,
+        ),
+        None => backtest::read_points(
+            args.format,
+            std::io::stdin().lock(),
+            &args.measurement,
+            ticker_field,
+        ),
+    }
+    .map_err(to_io)?;
+    info!("Read {} points", points.len());
+
+    let output = backtest.run(points).await.map_err(to_io)?;
+    info!("Writing {} points", output.len());
+
+    let format = args.output_format.unwrap_or(args.format);
+    match args.output {
+        Some(path) => backtest::write_points(format, BufWriter::new(File::create(path)?), &output),
+        None => backtest::write_points(format, std::io::stdout().lock(), &output),
+    }
+    .map_err(to_io)
+}
+
+/// Replays a recorded session and prints every differing response.
+///
+/// Exits with status 1 if the replay does not reproduce the recorded responses.
+async fn run_replay(session: PathBuf) -> io::Result<()> {
+    let session = Session::load(&session).await?;
+    info!(
+        "Replaying {} requests, {} responses recorded",
+        session.requests.len(),
+        session.responses.len()
+    );
+
+    let mismatches = session.replay().await;
+    for mismatch in &mismatches {
+        println!("Response {} differs", mismatch.index);
+        println!("  recorded: {:?}", mismatch.expected);
+        println!("  replayed: {:?}", mismatch.actual);
+    }
+
+    if mismatches.is_empty() {
+        println!("Replay matches the recorded responses");
+        Ok(())
+    } else {
+        println!("{} responses differ", mismatches.len());
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,228 @@
+//! Recording and offline replay of Kapacitor sessions.
+//!
+//! A session is recorded as two files holding the raw, length-prefixed protobuf
+//! streams of a connection: `<session>.requests` with everything Kapacitor sent
+//! and `<session>.responses` with everything the UDF answered. Replaying a
+//! session feeds the recorded requests to a fresh `IndicatorHandler` and diffs
+//! its responses against the recorded ones, so a misbehaving session from
+//! production can be reproduced deterministically.
+
+use crate::handler::{config::IndicatorOptions, indicator_handler::IndicatorHandler, snapshot};
+use async_std::{
+    channel,
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use kapacitor_udf::{
+    io::read_message,
+    proto::{request, response, ErrorResponse, Request, Response, SnapshotResponse},
+    traits::Handler,
+};
+use prost::Message;
+use tracing::{debug, warn};
+
+pub mod recorder;
+
+/// File extension of the recorded requests of a session.
+pub const REQUESTS_EXTENSION: &str = "requests";
+/// File extension of the recorded responses of a session.
+pub const RESPONSES_EXTENSION: &str = "responses";
+
+/// The messages exchanged during a recorded session.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pub requests: Vec<Request>,
+    pub responses: Vec<Response>,
+}
+
+/// A position at which the replayed responses differ from the recorded ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    /// Index of the response, not counting keepalives.
+    pub index: usize,
+    /// The recorded response, `None` if the replay produced more responses.
+    pub expected: Option<Response>,
+    /// The replayed response, `None` if the replay produced fewer responses.
+    pub actual: Option<Response>,
+}
+
+impl Session {
+    /// Loads a session from its `.requests` and `.responses` files.
+    ///
+    /// `path` may name either file or their common path without extension. A
+    /// session cut off in the middle of a message, e.g. because the UDF was
+    /// killed, is read up to the last complete message.
+    pub async fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let base = session_base(path.as_ref());
+        let requests = read_messages(&base.with_extension(REQUESTS_EXTENSION)).await?;
+        let responses = read_messages(&base.with_extension(RESPONSES_EXTENSION)).await?;
+        debug!(
+            "Loaded session {:?} with {} requests and {} responses",
+            base,
+            requests.len(),
+            responses.len()
+        );
+        Ok(Session {
+            requests,
+            responses,
+        })
+    }
+
+    /// Replays the requests against a new handler and returns the differences
+    /// to the recorded responses. Keepalives are ignored on both sides.
+    pub async fn replay(&self) -> Vec<Mismatch> {
+        let actual = replay(&self.requests).await;
+        let expected: Vec<&Response> = self
+            .responses
+            .iter()
+            .filter(|r| !matches!(r.message, Some(response::Message::Keepalive(_))))
+            .collect();
+        let options = self.options();
+
+        let mut mismatches = Vec::new();
+        for index in 0..expected.len().max(actual.len()) {
+            let expected = expected.get(index).copied();
+            let actual = actual.get(index);
+            let equal = match (expected, actual) {
+                (Some(expected), Some(actual)) => same_response(expected, actual, &options),
+                _ => false,
+            };
+            if !equal {
+                mismatches.push(Mismatch {
+                    index,
+                    expected: expected.cloned(),
+                    actual: actual.cloned(),
+                });
+            }
+        }
+        mismatches
+    }
+
+    /// Returns the options of the last valid Init request of the session.
+    fn options(&self) -> IndicatorOptions {
+        self.requests
+            .iter()
+            .rev()
+            .find_map(|r| match &r.message {
+                Some(request::Message::Init(init)) => {
+                    IndicatorOptions::from_proto_options(&init.options).ok()
+                }
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Feeds requests to a new `IndicatorHandler` the way the agent does and
+/// returns all responses in the order they would be written to the socket.
+///
+/// Keepalive requests are skipped. Like the agent, the replay stops with an
+/// error response at the first request the handler fails.
+pub async fn replay(requests: &[Request]) -> Vec<Response> {
+    let (sender, receiver) = channel::unbounded();
+    let mut handler =
+        IndicatorHandler::new(Arc::new(Mutex::new(sender)), IndicatorOptions::default()).await;
+
+    let mut responses = Vec::new();
+    for request in requests {
+        let result = process_request(&mut handler, request).await;
+        while let Ok(response) = receiver.try_recv() {
+            responses.push(response);
+        }
+        match result {
+            Ok(Some(response)) => responses.push(response),
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Replayed request failed: {}", e);
+                responses.push(Response {
+                    message: Some(response::Message::Error(ErrorResponse {
+                        error: e.to_string(),
+                    })),
+                });
+                break;
+            }
+        }
+    }
+
+    handler.stop().await;
+    responses
+}
+
+async fn process_request(
+    handler: &mut IndicatorHandler,
+    request: &Request,
+) -> io::Result<Option<Response>> {
+    let message = match &request.message {
+        Some(request::Message::Info(_)) => response::Message::Info(handler.info().await?),
+        Some(request::Message::Init(init)) => response::Message::Init(handler.init(init).await?),
+        Some(request::Message::Keepalive(_)) => return Ok(None),
+        Some(request::Message::Snapshot(_)) => {
+            response::Message::Snapshot(handler.snapshot().await?)
+        }
+        Some(request::Message::Restore(restore)) => {
+            response::Message::Restore(handler.restore(restore).await?)
+        }
+        Some(request::Message::Begin(begin)) => {
+            handler.begin_batch(begin).await?;
+            return Ok(None);
+        }
+        Some(request::Message::Point(point)) => {
+            handler.point(point).await?;
+            return Ok(None);
+        }
+        Some(request::Message::End(end)) => {
+            handler.end_batch(end).await?;
+            return Ok(None);
+        }
+        None => return Ok(None),
+    };
+    Ok(Some(Response {
+        message: Some(message),
+    }))
+}
+
+/// Compares two responses, comparing snapshots by their decoded state since
+/// the order of tickers in an encoded snapshot is not deterministic.
+fn same_response(expected: &Response, actual: &Response, options: &IndicatorOptions) -> bool {
+    match (&expected.message, &actual.message) {
+        (
+            Some(response::Message::Snapshot(SnapshotResponse { snapshot: expected })),
+            Some(response::Message::Snapshot(SnapshotResponse { snapshot: actual })),
+        ) => {
+            let decode = |bytes: &[u8]| {
+                snapshot::decode(bytes, options)
+                    .ok()
+                    .and_then(|states| serde_json::to_value(states).ok())
+            };
+            match (decode(expected), decode(actual)) {
+                (Some(expected), Some(actual)) => expected == actual,
+                _ => expected == actual,
+            }
+        }
+        _ => expected == actual,
+    }
+}
+
+fn session_base(path: &Path) -> PathBuf {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(REQUESTS_EXTENSION) | Some(RESPONSES_EXTENSION) => path.with_extension(""),
+        _ => path.to_path_buf(),
+    }
+}
+
+async fn read_messages<M: Message + Default>(path: &Path) -> io::Result<Vec<M>> {
+    let mut reader = BufReader::new(File::open(path).await?);
+    let mut buf = Vec::new();
+    let mut messages = Vec::new();
+    loop {
+        let mut message = M::default();
+        match read_message(&mut buf, &mut reader, &mut message).await {
+            Ok(()) => messages.push(message),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(messages)
+}
@@ -0,0 +1,124 @@
+//! Recording of the raw byte streams of a UDF connection.
+
+use async_std::{
+    channel::{self, Receiver, Sender, TrySendError},
+    fs,
+    io::{BufWriter, Read, Write, WriteExt},
+    task::{self, JoinHandle},
+};
+use std::{
+    fs::File,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tracing::warn;
+
+/// Number of reads or writes the log may fall behind the stream before
+/// recording stops, bounding the memory held for a slow disk.
+const MAX_PENDING_CHUNKS: usize = 4096;
+
+/// A stream wrapper that copies every byte read from or written to the stream into a file.
+///
+/// Wrapping the reader of a connection records the length-prefixed requests
+/// from Kapacitor, wrapping the writer records the responses, byte for byte as
+/// they crossed the socket. The file is written by a task of its own through a
+/// buffer that is flushed whenever the stream goes idle, so the stream never
+/// waits for the disk. If writing to the file fails or falls too far behind,
+/// recording stops with a warning while the stream itself keeps working.
+#[derive(Debug)]
+pub struct Recorder<S> {
+    inner: S,
+    log: Option<Sender<Vec<u8>>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl<S> Recorder<S> {
+    /// Creates a new `Recorder` copying the traffic of `inner` into `log`.
+    pub fn new(inner: S, log: File) -> Self {
+        let (sender, receiver) = channel::bounded(MAX_PENDING_CHUNKS);
+        Recorder {
+            inner,
+            log: Some(sender),
+            writer: Some(task::spawn(write_log(receiver, fs::File::from(log)))),
+        }
+    }
+
+    /// Stops recording and waits until everything recorded so far is written to the file.
+    pub async fn finish(mut self) {
+        self.log = None;
+        if let Some(writer) = self.writer.take() {
+            writer.await;
+        }
+    }
+
+    fn record(&mut self, bytes: &[u8]) {
+        let Some(log) = &self.log else {
+            return;
+        };
+        match log.try_send(bytes.to_vec()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                warn!("Recording cannot keep up with connection traffic, stopping recording");
+                self.log = None;
+            }
+            // The writer already warned about the failure that stopped it
+            Err(TrySendError::Closed(_)) => self.log = None,
+        }
+    }
+}
+
+/// Writes the recorded chunks to the file until the recorder is dropped or writing fails.
+async fn write_log(chunks: Receiver<Vec<u8>>, file: fs::File) {
+    let mut file = BufWriter::new(file);
+    while let Ok(chunk) = chunks.recv().await {
+        let mut result = file.write_all(&chunk).await;
+        if result.is_ok() && chunks.is_empty() {
+            result = file.flush().await;
+        }
+        if let Err(e) = result {
+            warn!(
+                "Failed to record connection traffic, stopping recording: {}",
+                e
+            );
+            return;
+        }
+    }
+    if let Err(e) = file.flush().await {
+        warn!("Failed to record connection traffic: {}", e);
+    }
+}
+
+impl<S: Read + Unpin> Read for Recorder<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let n = ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.record(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<S: Write + Unpin> Write for Recorder<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let n = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        this.record(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
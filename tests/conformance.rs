@@ -0,0 +1,1961 @@
+//! Golden-data conformance of the indicators with their industry-standard definitions.
+//!
+//! The input is the 30-day close series of the StockCharts moving average
+//! example. Reference values were computed with exact rational arithmetic from
+//! the published definitions, independently of this crate, and rounded to 12
+//! decimals:
+//!
+//! - `SMA_10`: the rolling mean, as TA-Lib `SMA` and pandas `rolling(10).mean()`.
+//! - `EMA_10`: pandas `ewm(span=10, adjust=False).mean()`, which seeds with the
+//!   first value. This is the definition the `EMA` indicator implements.
+//! - `TALIB_EMA_10`: TA-Lib `EMA`, which seeds with the SMA of the first 10
+//!   values and emits nothing before. The `EMA` indicator deliberately differs
+//!   from it during warm-up; the tests check how quickly the difference decays.
+//! - `ROC_10` and `MOM_10`: TA-Lib `ROC` and `MOM`.
+//! - `TRIX_5`: the percentage change of three chained pandas
+//!   `ewm(span=5, adjust=False)`, the seeding the `TRIX` indicator shares with
+//!   `EMA`.
+//! - `STDDEV_10`, `VARIANCE_10`, `MIN_10`, `MAX_10` and `MEDIAN_10`: the
+//!   population statistics of the last 10 days, as TA-Lib `STDDEV`, `VAR`,
+//!   `MIN` and `MAX` and pandas `rolling(10).median()`.
+//! - `ZSCORE_10`: the close minus `SMA_10`, divided by `STDDEV_10`.
+//! - `PERCENTRANK_10`: the percentage of the previous 10 closes less than or
+//!   equal to the close.
+//! - `RETURN_10` and `LOGRETURN_10`: the simple and log return over 10 days.
+//! - `REALIZEDVOL_10`: the sample standard deviation of the last 10 daily log
+//!   returns, as pandas `rolling(10).std()`, annualized over 252 days.
+//! - `LINREG_10`, `LINREG_10_SLOPE`, `LINREG_10_INTERCEPT` and `TSF_10`: TA-Lib
+//!   `LINEARREG`, `LINEARREG_SLOPE`, `LINEARREG_INTERCEPT` and `TSF`, and
+//!   `LINREG_10_R2` the coefficient of determination of the same fit.
+//!
+//! Indicators on high/low/close are checked against a 30-day series of bars,
+//! with references following TA-Lib's output positions and, except for the
+//! directional movement system, its definitions:
+//!
+//! - `PLUS_DI_14`, `MINUS_DI_14` and `ADX_14`: Wilder's directional movement
+//!   system with Wilder's original seeding, as in the StockCharts ADX
+//!   worksheet: the smoothed true range and directional movement start out as
+//!   the sum of the first 14 values, the ADX as the mean of the first 14 DX
+//!   values. TA-Lib `PLUS_DI`, `MINUS_DI` and `ADX` seed with the sum of the
+//!   first 13 values and one smoothing step instead, giving a +DI and -DI of
+//!   6.9295 and 32.1355 rather than 6.6617 and 32.3353 on day 14, a
+//!   difference that decays with Wilder's smoothing.
+//! - `AROON_UP_14` and `AROON_DOWN_14`: TA-Lib `AROON`.
+//! - `SAR`: TA-Lib `SAR` with the default acceleration of 0.02 up to 0.2.
+//! - `SUPERTREND_5_1`: SuperTrend with a multiplier of 1 on TA-Lib's `ATR`
+//!   over 5 days, starting out in an uptrend as pandas-ta does.
+//! - `KELTNER_10_5_2`: Keltner Channels around the `EMA` of the close over 10
+//!   days, seeded like `EMA_10`, with bands twice TA-Lib's `ATR` over 5 days.
+//! - `DONCHIAN_10_UPPER` and `DONCHIAN_10_LOWER`: the highest high and lowest
+//!   low of the last 10 days, as TA-Lib `MAX` and `MIN`.
+//! - `CCI_10`: TA-Lib `CCI`.
+//! - `ULTOSC_3_6_12`: TA-Lib `ULTOSC` over 3, 6 and 12 days.
+//! - `TENKAN_3`, `KIJUN_6`, `SENKOU_A_3_6`, `SENKOU_B_12` and `CHIKOU`:
+//!   Ichimoku over 3, 6 and 12 days, displaced by 6 days, on the days they are
+//!   plotted at.
+//! - `PARKINSON_10` and `GARMANKLASS_10`: Parkinson and Garman-Klass
+//!   volatility over 10 days, annualized over 252 days, with each bar opening
+//!   at the previous close.
+//!
+//! References involving logarithms were computed in double precision instead.
+
+use kapacitor_multi_indicator_batch_udf::harness::{bar, point, BatchOutput, HandlerHarness};
+
+const TOLERANCE: f64 = 1e-9;
+
+const CLOSES: [f64; 30] = [
+    22.27, 22.19, 22.08, 22.17, 22.18, 22.13, 22.23, 22.43, 22.24, 22.29, 22.15, 22.39, 22.38,
+    22.61, 23.36, 24.05, 23.75, 23.83, 23.95, 23.63, 23.82, 23.87, 23.65, 23.19, 23.10, 23.33,
+    22.68, 23.10, 22.40, 22.17,
+];
+
+const SMA_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(22.221000000000),
+    Some(22.209000000000),
+    Some(22.229000000000),
+    Some(22.259000000000),
+    Some(22.303000000000),
+    Some(22.421000000000),
+    Some(22.613000000000),
+    Some(22.765000000000),
+    Some(22.905000000000),
+    Some(23.076000000000),
+    Some(23.210000000000),
+    Some(23.377000000000),
+    Some(23.525000000000),
+    Some(23.652000000000),
+    Some(23.710000000000),
+    Some(23.684000000000),
+    Some(23.612000000000),
+    Some(23.505000000000),
+    Some(23.432000000000),
+    Some(23.277000000000),
+    Some(23.131000000000),
+];
+
+const EMA_10: [f64; 30] = [
+    22.270000000000,
+    22.255454545455,
+    22.223553719008,
+    22.213816679189,
+    22.207668192063,
+    22.193546702597,
+    22.200174574852,
+    22.241961015788,
+    22.241604467463,
+    22.250403655197,
+    22.232148445161,
+    22.260848727859,
+    22.282512595521,
+    22.342055759972,
+    22.527136530886,
+    22.804020797998,
+    22.976017016544,
+    23.131286649899,
+    23.280143622645,
+    23.343753873073,
+    23.430344077969,
+    23.510281518338,
+    23.535684878640,
+    23.472833082524,
+    23.405045249338,
+    23.391400658549,
+    23.262055084267,
+    23.232590523492,
+    23.081210428311,
+    22.915535804982,
+];
+
+const TALIB_EMA_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(22.221000000000),
+    Some(22.208090909091),
+    Some(22.241165289256),
+    Some(22.266407963937),
+    Some(22.328879243221),
+    Some(22.516355744454),
+    Some(22.795200154553),
+    Some(22.968800126452),
+    Some(23.125381921643),
+    Some(23.275312481344),
+    Some(23.339801121100),
+    Some(23.427110008173),
+    Some(23.507635461232),
+    Some(23.533519922826),
+    Some(23.471061755040),
+    Some(23.403595981396),
+    Some(23.390214893870),
+    Some(23.261084913166),
+    Some(23.231796747136),
+    Some(23.080560974929),
+    Some(22.915004434033),
+];
+
+const BARS: [(f64, f64, f64); 30] = [
+    (30.20, 29.41, 29.87),
+    (30.28, 29.32, 30.24),
+    (30.45, 29.96, 30.10),
+    (29.35, 28.74, 28.90),
+    (29.35, 28.56, 28.92),
+    (29.29, 28.41, 28.48),
+    (28.83, 28.08, 28.56),
+    (28.73, 27.43, 27.56),
+    (28.67, 27.66, 28.47),
+    (28.85, 27.83, 28.28),
+    (28.64, 27.40, 27.49),
+    (27.68, 27.09, 27.23),
+    (27.21, 26.18, 26.35),
+    (26.87, 26.13, 26.33),
+    (27.41, 26.63, 27.03),
+    (26.94, 26.13, 26.22),
+    (26.52, 25.43, 26.01),
+    (26.52, 25.35, 25.46),
+    (27.09, 25.88, 27.03),
+    (27.69, 26.96, 27.45),
+    (28.45, 27.14, 28.36),
+    (28.53, 28.01, 28.43),
+    (28.67, 27.88, 27.95),
+    (29.01, 27.99, 29.01),
+    (29.87, 28.76, 29.38),
+    (29.80, 29.14, 29.36),
+    (29.75, 28.71, 28.91),
+    (30.65, 28.93, 30.61),
+    (30.60, 30.03, 30.05),
+    (30.76, 29.39, 30.19),
+];
+
+const PLUS_DI_14: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(6.661676646707),
+    Some(6.211080094481),
+    Some(5.707552897605),
+    Some(5.218510602864),
+    Some(8.607418178209),
+    Some(12.439537158377),
+    Some(16.607016492051),
+    Some(16.561031357150),
+    Some(16.628315565010),
+    Some(17.822424146945),
+    Some(22.605909522950),
+    Some(21.501883387247),
+    Some(19.856265400188),
+    Some(23.718186893672),
+    Some(22.729402031981),
+    Some(20.550126792049),
+];
+
+const MINUS_DI_14: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(32.335329341317),
+    Some(33.905948035216),
+    Some(36.363498375884),
+    Some(33.833620858990),
+    Some(29.979641875130),
+    Some(28.418365123919),
+    Some(25.819768254356),
+    Some(24.848462257449),
+    Some(23.407766935904),
+    Some(21.598280766331),
+    Some(19.866406357969),
+    Some(18.896171923499),
+    Some(20.614351368328),
+    Some(18.116192555043),
+    Some(17.360948613750),
+    Some(20.175420948221),
+];
+
+const ADX_14: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(33.707888495997),
+    Some(32.256674079437),
+    Some(30.018345619444),
+];
+
+const AROON_UP_14: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(14.285714285714),
+    Some(7.142857142857),
+    Some(0.000000000000),
+    Some(7.142857142857),
+    Some(0.000000000000),
+    Some(0.000000000000),
+    Some(21.428571428571),
+    Some(14.285714285714),
+    Some(7.142857142857),
+    Some(100.000000000000),
+    Some(100.000000000000),
+    Some(92.857142857143),
+    Some(85.714285714286),
+    Some(100.000000000000),
+    Some(92.857142857143),
+    Some(100.000000000000),
+];
+
+const AROON_DOWN_14: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(92.857142857143),
+    Some(100.000000000000),
+    Some(100.000000000000),
+    Some(100.000000000000),
+    Some(92.857142857143),
+    Some(85.714285714286),
+    Some(78.571428571429),
+    Some(71.428571428571),
+    Some(64.285714285714),
+    Some(57.142857142857),
+    Some(50.000000000000),
+    Some(42.857142857143),
+    Some(35.714285714286),
+    Some(28.571428571429),
+    Some(21.428571428571),
+    Some(14.285714285714),
+];
+
+const SAR: [Option<f64>; 30] = [
+    None,
+    Some(29.320000000000),
+    Some(29.320000000000),
+    Some(30.450000000000),
+    Some(30.450000000000),
+    Some(30.374400000000),
+    Some(30.256536000000),
+    Some(30.082413120000),
+    Some(29.817171808000),
+    Some(29.578454627200),
+    Some(29.363609164480),
+    Some(29.127976064742),
+    Some(28.842659415678),
+    Some(28.416633909170),
+    Some(28.005039805519),
+    Some(27.667532640526),
+    Some(27.410000000000),
+    Some(27.014000000000),
+    Some(25.350000000000),
+    Some(25.350000000000),
+    Some(25.443600000000),
+    Some(25.623984000000),
+    Some(25.856465280000),
+    Some(26.137818752000),
+    Some(26.482480501760),
+    Some(26.956733231514),
+    Some(27.364590579102),
+    Some(27.715347898027),
+    Some(28.184892234343),
+    Some(28.579309476848),
+];
+
+const SAR_DIRECTION: [Option<i64>; 30] = [
+    None,
+    Some(1),
+    Some(1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+];
+
+const SUPERTREND_5_1: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(27.954000000000),
+    Some(27.954000000000),
+    Some(29.033440000000),
+    Some(29.033440000000),
+    Some(29.033440000000),
+    Some(29.033440000000),
+    Some(28.336153024000),
+    Some(27.665922419200),
+    Some(27.424737935360),
+    Some(27.424737935360),
+    Some(27.424737935360),
+    Some(26.948705822904),
+    Some(26.947964658323),
+    Some(25.348628273341),
+    Some(26.269902618673),
+    Some(26.688922094938),
+    Some(27.281137675951),
+    Some(27.325910140761),
+    Some(27.528728112608),
+    Some(28.315982490087),
+    Some(28.538785992069),
+    Some(28.538785992069),
+    Some(28.679623034924),
+    Some(29.310698427940),
+    Some(29.310698427940),
+];
+
+const SUPERTREND_5_1_DIRECTION: [Option<i64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(1),
+    Some(1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(-1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+    Some(1),
+];
+
+const KELTNER_10_5_2: [Option<f64>; 30] = [
+    Some(29.870000000000),
+    Some(29.937272727273),
+    Some(29.966859504132),
+    Some(29.772885048835),
+    Some(29.617815039956),
+    Some(29.410939578146),
+    Some(29.256223291210),
+    Some(28.947819056445),
+    Some(28.860942864364),
+    Some(28.755316889025),
+    Some(28.525259272839),
+    Some(28.289757586868),
+    Some(27.937074389256),
+    Some(27.644879045755),
+    Some(27.533082855617),
+    Some(27.294340518232),
+    Some(27.060824060372),
+    Some(26.769765140304),
+    Some(26.817080569340),
+    Some(26.932156829460),
+    Some(27.191764678649),
+    Some(27.416898373440),
+    Some(27.513825941906),
+    Some(27.785857588832),
+    Some(28.075701663590),
+    Some(28.309210452028),
+    Some(28.418444915296),
+    Some(28.816909476151),
+    Some(29.041107753214),
+    Some(29.249997252630),
+];
+
+const KELTNER_10_5_2_UPPER: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(31.202939578146),
+    Some(30.989823291210),
+    Some(30.854699056445),
+    Some(30.830446864364),
+    Some(30.738920089025),
+    Some(30.608141832839),
+    Some(30.192063634868),
+    Some(29.878919227656),
+    Some(29.494354916475),
+    Some(29.444663552193),
+    Some(29.183605075493),
+    Some(29.008235706181),
+    Some(28.795694456951),
+    Some(29.089824022657),
+    Some(29.042351592114),
+    Some(29.403920488772),
+    Some(29.394623021539),
+    Some(29.412005660384),
+    Some(29.728401363615),
+    Some(30.073736683416),
+    Some(30.171638467889),
+    Some(30.324387327985),
+    Some(31.037663406302),
+    Some(31.049710897335),
+    Some(31.404879767927),
+];
+
+const KELTNER_10_5_2_LOWER: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(27.618939578146),
+    Some(27.522623291210),
+    Some(27.040939056445),
+    Some(26.891438864364),
+    Some(26.771713689025),
+    Some(26.442376712839),
+    Some(26.387451538868),
+    Some(25.995229550856),
+    Some(25.795403175035),
+    Some(25.621502159041),
+    Some(25.405075960972),
+    Some(25.113412414563),
+    Some(24.743835823657),
+    Some(24.544337116022),
+    Some(24.821962066806),
+    Some(24.979608868526),
+    Some(25.439173725342),
+    Some(25.615646223427),
+    Some(25.843313814049),
+    Some(26.077666643763),
+    Some(26.446782436167),
+    Some(26.512502502607),
+    Some(26.596155546000),
+    Some(27.032504609093),
+    Some(27.095114737333),
+];
+
+const DONCHIAN_10_UPPER: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(30.450000000000),
+    Some(30.450000000000),
+    Some(30.450000000000),
+    Some(29.350000000000),
+    Some(29.350000000000),
+    Some(29.290000000000),
+    Some(28.850000000000),
+    Some(28.850000000000),
+    Some(28.850000000000),
+    Some(28.850000000000),
+    Some(28.640000000000),
+    Some(28.450000000000),
+    Some(28.530000000000),
+    Some(28.670000000000),
+    Some(29.010000000000),
+    Some(29.870000000000),
+    Some(29.870000000000),
+    Some(29.870000000000),
+    Some(30.650000000000),
+    Some(30.650000000000),
+    Some(30.760000000000),
+];
+
+const DONCHIAN_10_LOWER: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(27.430000000000),
+    Some(27.400000000000),
+    Some(27.090000000000),
+    Some(26.180000000000),
+    Some(26.130000000000),
+    Some(26.130000000000),
+    Some(26.130000000000),
+    Some(25.430000000000),
+    Some(25.350000000000),
+    Some(25.350000000000),
+    Some(25.350000000000),
+    Some(25.350000000000),
+    Some(25.350000000000),
+    Some(25.350000000000),
+    Some(25.350000000000),
+    Some(25.350000000000),
+    Some(25.350000000000),
+    Some(25.350000000000),
+    Some(25.880000000000),
+    Some(26.960000000000),
+    Some(27.140000000000),
+];
+
+const ROC_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(-0.538841490795),
+    Some(0.901306894998),
+    Some(1.358695652174),
+    Some(1.984663960307),
+    Some(5.320108205591),
+    Some(8.676005422503),
+    Some(6.837606837607),
+    Some(6.241640659831),
+    Some(7.688848920863),
+    Some(6.011664423508),
+    Some(7.539503386005),
+    Some(6.610093791871),
+    Some(5.674709562109),
+    Some(2.565236620964),
+    Some(-1.113013698630),
+    Some(-2.993762993763),
+    Some(-4.505263157895),
+    Some(-3.063365505665),
+    Some(-6.471816283925),
+    Some(-6.178586542531),
+];
+
+const MOM_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(-0.120000000000),
+    Some(0.200000000000),
+    Some(0.300000000000),
+    Some(0.440000000000),
+    Some(1.180000000000),
+    Some(1.920000000000),
+    Some(1.520000000000),
+    Some(1.400000000000),
+    Some(1.710000000000),
+    Some(1.340000000000),
+    Some(1.670000000000),
+    Some(1.480000000000),
+    Some(1.270000000000),
+    Some(0.580000000000),
+    Some(-0.260000000000),
+    Some(-0.720000000000),
+    Some(-1.070000000000),
+    Some(-0.730000000000),
+    Some(-1.550000000000),
+    Some(-1.460000000000),
+];
+
+const TRIX_5: [Option<f64>; 30] = [
+    None,
+    Some(-0.013304728168),
+    Some(-0.044909432644),
+    Some(-0.057132714303),
+    Some(-0.056672037869),
+    Some(-0.058802042584),
+    Some(-0.042311343577),
+    Some(0.010358131244),
+    Some(0.028026730818),
+    Some(0.038030030255),
+    Some(0.018424435188),
+    Some(0.034446639444),
+    Some(0.053909059054),
+    Some(0.105610544107),
+    Some(0.274133143993),
+    Some(0.536812291173),
+    Some(0.686850801169),
+    Some(0.750385104047),
+    Some(0.762281536232),
+    Some(0.674942729952),
+    Some(0.586908607138),
+    Some(0.507611673272),
+    Some(0.397652115929),
+    Some(0.220111490092),
+    Some(0.046855555552),
+    Some(-0.045765049352),
+    Some(-0.191391326918),
+    Some(-0.242135891081),
+    Some(-0.353552500227),
+    Some(-0.478519150910),
+];
+
+const CCI_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(-68.686434608222),
+    Some(-101.534262742495),
+    Some(-136.990055594707),
+    Some(-179.428221046984),
+    Some(-143.854748603352),
+    Some(-65.815324165029),
+    Some(-98.210609659541),
+    Some(-113.475177304965),
+    Some(-107.764567738148),
+    Some(-18.327001547770),
+    Some(80.111683848797),
+    Some(152.812447994675),
+    Some(149.615410795725),
+    Some(101.410934744268),
+    Some(110.561566202988),
+    Some(121.326397919376),
+    Some(104.799731013323),
+    Some(76.170920113425),
+    Some(127.257799671593),
+    Some(117.926440776137),
+    Some(93.564993564994),
+];
+
+const ULTOSC_3_6_12: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(21.470831954041),
+    Some(26.148591745809),
+    Some(34.675352966281),
+    Some(32.618789874028),
+    Some(40.231444846720),
+    Some(27.675988328480),
+    Some(52.929983142934),
+    Some(56.814213071854),
+    Some(74.667895829585),
+    Some(73.843333712429),
+    Some(62.414676641784),
+    Some(68.125854004825),
+    Some(62.263725887870),
+    Some(65.766342936337),
+    Some(43.344747027028),
+    Some(61.675611935571),
+    Some(59.199287258833),
+    Some(64.230727766029),
+];
+
+/// Days of the Ichimoku outputs, including the shifted ones before and after the series.
+const ICHIMOKU_DAYS: [i64; 42] = [
+    -6, -5, -4, -3, -2, -1, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+    20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35,
+];
+
+const TENKAN_3: [Option<f64>; 42] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(29.885000000000),
+    Some(29.595000000000),
+    Some(29.505000000000),
+    Some(28.880000000000),
+    Some(28.715000000000),
+    Some(28.360000000000),
+    Some(28.130000000000),
+    Some(28.140000000000),
+    Some(28.125000000000),
+    Some(27.970000000000),
+    Some(27.410000000000),
+    Some(26.905000000000),
+    Some(26.770000000000),
+    Some(26.770000000000),
+    Some(26.420000000000),
+    Some(26.145000000000),
+    Some(26.220000000000),
+    Some(26.520000000000),
+    Some(27.165000000000),
+    Some(27.745000000000),
+    Some(27.905000000000),
+    Some(28.445000000000),
+    Some(28.875000000000),
+    Some(28.930000000000),
+    Some(29.290000000000),
+    Some(29.680000000000),
+    Some(29.680000000000),
+    Some(29.845000000000),
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+];
+
+const KIJUN_6: [Option<f64>; 42] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(29.430000000000),
+    Some(29.265000000000),
+    Some(28.940000000000),
+    Some(28.390000000000),
+    Some(28.390000000000),
+    Some(28.345000000000),
+    Some(27.970000000000),
+    Some(27.515000000000),
+    Some(27.490000000000),
+    Some(27.490000000000),
+    Some(27.385000000000),
+    Some(26.555000000000),
+    Some(26.380000000000),
+    Some(26.380000000000),
+    Some(26.520000000000),
+    Some(26.900000000000),
+    Some(26.940000000000),
+    Some(27.010000000000),
+    Some(27.445000000000),
+    Some(28.415000000000),
+    Some(28.505000000000),
+    Some(28.875000000000),
+    Some(29.265000000000),
+    Some(29.320000000000),
+    Some(29.735000000000),
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+];
+
+const SENKOU_A_3_6: [Option<f64>; 42] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(29.155000000000),
+    Some(28.990000000000),
+    Some(28.650000000000),
+    Some(28.260000000000),
+    Some(28.265000000000),
+    Some(28.235000000000),
+    Some(27.970000000000),
+    Some(27.462500000000),
+    Some(27.197500000000),
+    Some(27.130000000000),
+    Some(27.077500000000),
+    Some(26.487500000000),
+    Some(26.262500000000),
+    Some(26.300000000000),
+    Some(26.520000000000),
+    Some(27.032500000000),
+    Some(27.342500000000),
+    Some(27.457500000000),
+    Some(27.945000000000),
+    Some(28.645000000000),
+    Some(28.717500000000),
+    Some(29.082500000000),
+    Some(29.472500000000),
+    Some(29.500000000000),
+    Some(29.790000000000),
+];
+
+const SENKOU_B_12: [Option<f64>; 42] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(28.770000000000),
+    Some(28.315000000000),
+    Some(28.290000000000),
+    Some(27.740000000000),
+    Some(27.740000000000),
+    Some(27.360000000000),
+    Some(27.100000000000),
+    Some(27.100000000000),
+    Some(27.100000000000),
+    Some(27.100000000000),
+    Some(26.995000000000),
+    Some(27.010000000000),
+    Some(27.180000000000),
+    Some(27.610000000000),
+    Some(27.610000000000),
+    Some(27.610000000000),
+    Some(28.000000000000),
+    Some(28.000000000000),
+    Some(28.320000000000),
+];
+
+const CHIKOU: [Option<f64>; 42] = [
+    Some(29.870000000000),
+    Some(30.240000000000),
+    Some(30.100000000000),
+    Some(28.900000000000),
+    Some(28.920000000000),
+    Some(28.480000000000),
+    Some(28.560000000000),
+    Some(27.560000000000),
+    Some(28.470000000000),
+    Some(28.280000000000),
+    Some(27.490000000000),
+    Some(27.230000000000),
+    Some(26.350000000000),
+    Some(26.330000000000),
+    Some(27.030000000000),
+    Some(26.220000000000),
+    Some(26.010000000000),
+    Some(25.460000000000),
+    Some(27.030000000000),
+    Some(27.450000000000),
+    Some(28.360000000000),
+    Some(28.430000000000),
+    Some(27.950000000000),
+    Some(29.010000000000),
+    Some(29.380000000000),
+    Some(29.360000000000),
+    Some(28.910000000000),
+    Some(30.610000000000),
+    Some(30.050000000000),
+    Some(30.190000000000),
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+];
+
+const STDDEV_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(0.092027169901),
+    Some(0.092676857953),
+    Some(0.106906501205),
+    Some(0.102902866821),
+    Some(0.142059846544),
+    Some(0.341275548494),
+    Some(0.580087062431),
+    Some(0.654220910702),
+    Some(0.714566302032),
+    Some(0.739150864168),
+    Some(0.705195008491),
+    Some(0.627902062427),
+    Some(0.547032905774),
+    Some(0.391887739027),
+    Some(0.250958163844),
+    Some(0.295404807002),
+    Some(0.284984210089),
+    Some(0.393350988304),
+    Some(0.394000000000),
+    Some(0.459217813243),
+    Some(0.547402046032),
+];
+
+const VARIANCE_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(0.008469000000),
+    Some(0.008589000000),
+    Some(0.011429000000),
+    Some(0.010589000000),
+    Some(0.020181000000),
+    Some(0.116469000000),
+    Some(0.336501000000),
+    Some(0.428005000000),
+    Some(0.510605000000),
+    Some(0.546344000000),
+    Some(0.497300000000),
+    Some(0.394261000000),
+    Some(0.299245000000),
+    Some(0.153576000000),
+    Some(0.062980000000),
+    Some(0.087264000000),
+    Some(0.081216000000),
+    Some(0.154725000000),
+    Some(0.155236000000),
+    Some(0.210881000000),
+    Some(0.299649000000),
+];
+
+const ZSCORE_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(0.749778571635),
+    Some(-0.636620633277),
+    Some(1.505988861150),
+    Some(1.175866171060),
+    Some(2.161061042015),
+    Some(2.751442358362),
+    Some(2.477214358096),
+    Some(1.505607637859),
+    Some(1.294491494168),
+    Some(1.182437905940),
+    Some(0.595579938802),
+    Some(0.705524040306),
+    Some(0.630675040492),
+    Some(-0.005103502357),
+    Some(-2.072058513797),
+    Some(-1.976948195009),
+    Some(-0.989528507253),
+    Some(-2.097363485869),
+    Some(-0.842639593909),
+    Some(-1.909769121990),
+    Some(-1.755565232111),
+];
+
+const PERCENTRANK_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(20.000000000000),
+    Some(90.000000000000),
+    Some(80.000000000000),
+    Some(100.000000000000),
+    Some(100.000000000000),
+    Some(100.000000000000),
+    Some(90.000000000000),
+    Some(90.000000000000),
+    Some(90.000000000000),
+    Some(60.000000000000),
+    Some(70.000000000000),
+    Some(80.000000000000),
+    Some(40.000000000000),
+    Some(10.000000000000),
+    Some(0.000000000000),
+    Some(20.000000000000),
+    Some(0.000000000000),
+    Some(20.000000000000),
+    Some(0.000000000000),
+    Some(0.000000000000),
+];
+
+const MIN_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(22.080000000000),
+    Some(22.080000000000),
+    Some(22.080000000000),
+    Some(22.130000000000),
+    Some(22.130000000000),
+    Some(22.130000000000),
+    Some(22.150000000000),
+    Some(22.150000000000),
+    Some(22.150000000000),
+    Some(22.150000000000),
+    Some(22.150000000000),
+    Some(22.380000000000),
+    Some(22.380000000000),
+    Some(22.610000000000),
+    Some(23.190000000000),
+    Some(23.100000000000),
+    Some(23.100000000000),
+    Some(22.680000000000),
+    Some(22.680000000000),
+    Some(22.400000000000),
+    Some(22.170000000000),
+];
+
+const MAX_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(22.430000000000),
+    Some(22.430000000000),
+    Some(22.430000000000),
+    Some(22.430000000000),
+    Some(22.610000000000),
+    Some(23.360000000000),
+    Some(24.050000000000),
+    Some(24.050000000000),
+    Some(24.050000000000),
+    Some(24.050000000000),
+    Some(24.050000000000),
+    Some(24.050000000000),
+    Some(24.050000000000),
+    Some(24.050000000000),
+    Some(24.050000000000),
+    Some(24.050000000000),
+    Some(23.950000000000),
+    Some(23.950000000000),
+    Some(23.950000000000),
+    Some(23.870000000000),
+    Some(23.870000000000),
+];
+
+const MEDIAN_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(22.210000000000),
+    Some(22.185000000000),
+    Some(22.205000000000),
+    Some(22.235000000000),
+    Some(22.265000000000),
+    Some(22.335000000000),
+    Some(22.385000000000),
+    Some(22.410000000000),
+    Some(22.500000000000),
+    Some(22.985000000000),
+    Some(23.495000000000),
+    Some(23.690000000000),
+    Some(23.785000000000),
+    Some(23.785000000000),
+    Some(23.785000000000),
+    Some(23.785000000000),
+    Some(23.700000000000),
+    Some(23.640000000000),
+    Some(23.480000000000),
+    Some(23.260000000000),
+    Some(23.145000000000),
+];
+
+const RETURN_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(-0.005388414908),
+    Some(0.009013068950),
+    Some(0.013586956522),
+    Some(0.019846639603),
+    Some(0.053201082056),
+    Some(0.086760054225),
+    Some(0.068376068376),
+    Some(0.062416406598),
+    Some(0.076888489209),
+    Some(0.060116644235),
+    Some(0.075395033860),
+    Some(0.066100937919),
+    Some(0.056747095621),
+    Some(0.025652366210),
+    Some(-0.011130136986),
+    Some(-0.029937629938),
+    Some(-0.045052631579),
+    Some(-0.030633655057),
+    Some(-0.064718162839),
+    Some(-0.061785865425),
+];
+
+const LOGRETURN_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(-0.005402984778),
+    Some(0.008972693666),
+    Some(0.013495481475),
+    Some(0.019652262662),
+    Some(0.051834176038),
+    Some(0.083200842489),
+    Some(0.066139802505),
+    Some(0.060545942579),
+    Some(0.074075854475),
+    Some(0.058378943801),
+    Some(0.072688067436),
+    Some(0.064008009740),
+    Some(0.055195412054),
+    Some(0.025328864978),
+    Some(-0.011192540432),
+    Some(-0.030394910518),
+    Some(-0.046099051621),
+    Some(-0.031112673583),
+    Some(-0.066907364996),
+    Some(-0.063777067571),
+];
+
+const REALIZEDVOL_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(0.087626392900),
+    Some(0.102098261107),
+    Some(0.097229726839),
+    Some(0.106517833184),
+    Some(0.186322511603),
+    Some(0.215500596090),
+    Some(0.239619044233),
+    Some(0.239733142969),
+    Some(0.225947314902),
+    Some(0.248593541392),
+    Some(0.239231531307),
+    Some(0.239635426017),
+    Some(0.250522473243),
+    Some(0.278156195650),
+    Some(0.222311734920),
+    Some(0.161889457834),
+    Some(0.201962955697),
+    Some(0.230520496756),
+    Some(0.262911055430),
+    Some(0.261120775698),
+];
+
+const PARKINSON_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(0.294475084673),
+    Some(0.313265521672),
+    Some(0.304828420570),
+    Some(0.322587471609),
+    Some(0.327325126034),
+    Some(0.328557832305),
+    Some(0.328577354783),
+    Some(0.343012919396),
+    Some(0.341575608052),
+    Some(0.352080747822),
+    Some(0.344488397051),
+    Some(0.347941480081),
+    Some(0.346293881501),
+    Some(0.336869728555),
+    Some(0.343565613714),
+    Some(0.351420480076),
+    Some(0.345810422308),
+    Some(0.339239625732),
+    Some(0.356224262120),
+    Some(0.333364350570),
+    Some(0.351440943311),
+];
+
+const GARMANKLASS_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(0.277436544870),
+    Some(0.291526708072),
+    Some(0.279919039636),
+    Some(0.288972841494),
+    Some(0.322304920904),
+    Some(0.313534892636),
+    Some(0.302653183292),
+    Some(0.323242771900),
+    Some(0.333228736694),
+    Some(0.310740590498),
+    Some(0.295550893701),
+    Some(0.296858767610),
+    Some(0.295566232756),
+    Some(0.293457187226),
+    Some(0.280981755312),
+    Some(0.302748768597),
+    Some(0.308611214690),
+    Some(0.295478988531),
+    Some(0.276309961509),
+    Some(0.293311304933),
+    Some(0.324494031406),
+];
+
+const LINREG_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(22.286727272727),
+    Some(22.272000000000),
+    Some(22.330727272727),
+    Some(22.353090909091),
+    Some(22.456545454545),
+    Some(22.797090909091),
+    Some(23.301636363636),
+    Some(23.617818181818),
+    Some(23.918727272727),
+    Some(24.146727272727),
+    Some(24.180909090909),
+    Some(24.179636363636),
+    Some(24.152545454545),
+    Some(23.966727272727),
+    Some(23.598727272727),
+    Some(23.318000000000),
+    Some(23.268909090909),
+    Some(22.974545454545),
+    Some(22.899636363636),
+    Some(22.646727272727),
+    Some(22.334909090909),
+];
+
+const LINREG_10_SLOPE: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(0.014606060606),
+    Some(0.014000000000),
+    Some(0.022606060606),
+    Some(0.020909090909),
+    Some(0.034121212121),
+    Some(0.083575757576),
+    Some(0.153030303030),
+    Some(0.189515151515),
+    Some(0.225272727273),
+    Some(0.237939393939),
+    Some(0.215757575758),
+    Some(0.178363636364),
+    Some(0.139454545455),
+    Some(0.069939393939),
+    Some(-0.024727272727),
+    Some(-0.081333333333),
+    Some(-0.076242424242),
+    Some(-0.117878787879),
+    Some(-0.118303030303),
+    Some(-0.140060606061),
+    Some(-0.176909090909),
+];
+
+const LINREG_10_INTERCEPT: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(22.155272727273),
+    Some(22.146000000000),
+    Some(22.127272727273),
+    Some(22.164909090909),
+    Some(22.149454545455),
+    Some(22.044909090909),
+    Some(21.924363636364),
+    Some(21.912181818182),
+    Some(21.891272727273),
+    Some(22.005272727273),
+    Some(22.239090909091),
+    Some(22.574363636364),
+    Some(22.897454545455),
+    Some(23.337272727273),
+    Some(23.821272727273),
+    Some(24.050000000000),
+    Some(23.955090909091),
+    Some(24.035454545455),
+    Some(23.964363636364),
+    Some(23.907272727273),
+    Some(23.927090909091),
+];
+
+const LINREG_10_R2: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(0.207820321529),
+    Some(0.188264058680),
+    Some(0.368888818184),
+    Some(0.340619339108),
+    Some(0.475948724648),
+    Some(0.494771010728),
+    Some(0.574146161752),
+    Some(0.692297845572),
+    Some(0.819947637873),
+    Some(0.854910148740),
+    Some(0.772267212645),
+    Some(0.665706450572),
+    Some(0.536157511556),
+    Some(0.262769119544),
+    Some(0.080094690955),
+    Some(0.625397261948),
+    Some(0.590480753158),
+    Some(0.740908846095),
+    Some(0.743794980390),
+    Some(0.767447187291),
+    Some(0.861670882205),
+];
+
+const TSF_10: [Option<f64>; 30] = [
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(22.301333333333),
+    Some(22.286000000000),
+    Some(22.353333333333),
+    Some(22.374000000000),
+    Some(22.490666666667),
+    Some(22.880666666667),
+    Some(23.454666666667),
+    Some(23.807333333333),
+    Some(24.144000000000),
+    Some(24.384666666667),
+    Some(24.396666666667),
+    Some(24.358000000000),
+    Some(24.292000000000),
+    Some(24.036666666667),
+    Some(23.574000000000),
+    Some(23.236666666667),
+    Some(23.192666666667),
+    Some(22.856666666667),
+    Some(22.781333333333),
+    Some(22.506666666667),
+    Some(22.158000000000),
+];
+
+/// Runs the close series through the handler and returns the forwarded batch.
+async fn close_outputs(options: &[(&str, &str)]) -> BatchOutput {
+    let mut harness = HandlerHarness::new().await;
+    let init = harness.init(options).await.unwrap();
+    assert!(init.success, "init failed: {}", init.error);
+
+    let points: Vec<_> = CLOSES
+        .iter()
+        .enumerate()
+        .map(|(day, close)| point(day as i64, "CS", *close))
+        .collect();
+    let mut batches = harness.batch(&points).await.unwrap();
+    batches.remove(0)
+}
+
+/// Runs the close series through the handler and returns the indicator value of every day.
+async fn indicator(options: &[(&str, &str)]) -> Vec<f64> {
+    close_outputs(options)
+        .await
+        .values("indicator")
+        .into_iter()
+        .map(|value| value.expect("every point carries the indicator"))
+        .collect()
+}
+
+/// Runs the bar series through the handler and returns the forwarded batch.
+async fn bar_outputs(options: &[(&str, &str)]) -> BatchOutput {
+    let mut harness = HandlerHarness::new().await;
+    let init = harness.init(options).await.unwrap();
+    assert!(init.success, "init failed: {}", init.error);
+
+    let points: Vec<_> = BARS
+        .iter()
+        .enumerate()
+        .map(|(day, (high, low, close))| bar(day as i64, "CS", *high, *low, *close))
+        .collect();
+    let mut batches = harness.batch(&points).await.unwrap();
+    batches.remove(0)
+}
+
+/// Compares an output series, including the days on which it is not available yet.
+fn assert_series(name: &str, actual: &[Option<f64>], expected: &[Option<f64>]) {
+    assert_eq!(actual.len(), expected.len(), "{}", name);
+    for (day, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+        match (actual, expected) {
+            (Some(actual), Some(expected)) => assert!(
+                (actual - expected).abs() < TOLERANCE,
+                "{} on day {} is {}, expected {}",
+                name,
+                day,
+                actual,
+                expected
+            ),
+            _ => assert_eq!(actual, expected, "{} on day {}", name, day),
+        }
+    }
+}
+
+#[async_std::test]
+async fn ema_matches_pandas_ewm_without_adjustment() {
+    let ema = indicator(&[("type", "EMA"), ("period", "10")]).await;
+
+    assert_eq!(ema.len(), EMA_10.len());
+    for (day, (actual, expected)) in ema.iter().zip(EMA_10).enumerate() {
+        assert!(
+            (actual - expected).abs() < TOLERANCE,
+            "day {}: EMA is {}, expected {}",
+            day,
+            actual,
+            expected
+        );
+    }
+}
+
+#[async_std::test]
+async fn ema_difference_to_talib_seeding_decays_geometrically() {
+    let ema = indicator(&[("type", "EMA"), ("period", "10")]).await;
+    let decay: f64 = 1.0 - 2.0 / 11.0;
+
+    let seed_day = 9;
+    let seed_difference = ema[seed_day] - TALIB_EMA_10[seed_day].unwrap();
+    for (day, talib) in TALIB_EMA_10.iter().enumerate().skip(seed_day) {
+        let expected = seed_difference * decay.powi((day - seed_day) as i32);
+        let actual = ema[day] - talib.unwrap();
+        assert!(
+            (actual - expected).abs() < TOLERANCE,
+            "day {}: difference to TA-Lib is {}, expected {}",
+            day,
+            actual,
+            expected
+        );
+    }
+}
+
+#[async_std::test]
+async fn sma_matches_rolling_mean_after_warm_up() {
+    let sma = indicator(&[("type", "SMA"), ("period", "10")]).await;
+
+    assert_eq!(sma.len(), SMA_10.len());
+    for (day, (actual, expected)) in sma.iter().zip(SMA_10).enumerate() {
+        // During warm-up the SMA passes the input through, where TA-Lib emits nothing
+        let expected = expected.unwrap_or(CLOSES[day]);
+        assert!(
+            (actual - expected).abs() < TOLERANCE,
+            "day {}: SMA is {}, expected {}",
+            day,
+            actual,
+            expected
+        );
+    }
+}
+
+#[async_std::test]
+async fn adx_matches_wilder_directional_movement() {
+    let batch = bar_outputs(&[("type", "ADX"), ("period", "14"), ("as", "adx")]).await;
+
+    assert_series("+DI", &batch.values("adx_plus_di"), &PLUS_DI_14);
+    assert_series("-DI", &batch.values("adx_minus_di"), &MINUS_DI_14);
+    assert_series("ADX", &batch.values("adx"), &ADX_14);
+}
+
+#[async_std::test]
+async fn aroon_matches_talib() {
+    let batch = bar_outputs(&[("type", "AROON"), ("period", "14"), ("as", "aroon")]).await;
+    let oscillator: Vec<_> = AROON_UP_14
+        .iter()
+        .zip(AROON_DOWN_14)
+        .map(|(up, down)| Some((*up)? - down?))
+        .collect();
+
+    assert_series("Aroon Up", &batch.values("aroon_up"), &AROON_UP_14);
+    assert_series("Aroon Down", &batch.values("aroon_down"), &AROON_DOWN_14);
+    assert_series("Aroon Oscillator", &batch.values("aroon"), &oscillator);
+}
+
+#[async_std::test]
+async fn sar_matches_talib() {
+    let batch = bar_outputs(&[("type", "SAR"), ("as", "sar")]).await;
+
+    assert_series("SAR", &batch.values("sar"), &SAR);
+    assert_eq!(batch.int_values("sar_direction"), SAR_DIRECTION);
+}
+
+#[async_std::test]
+async fn supertrend_follows_atr_bands() {
+    let batch = bar_outputs(&[
+        ("type", "SUPERTREND"),
+        ("period", "5"),
+        ("multiplier", "1"),
+        ("as", "st"),
+    ])
+    .await;
+
+    assert_series("SuperTrend", &batch.values("st"), &SUPERTREND_5_1);
+    assert_eq!(batch.int_values("st_direction"), SUPERTREND_5_1_DIRECTION);
+}
+
+#[async_std::test]
+async fn keltner_channels_surround_the_ema() {
+    let batch = bar_outputs(&[
+        ("type", "KELTNER"),
+        ("period", "10"),
+        ("atr_period", "5"),
+        ("multiplier", "2"),
+        ("as", "kc"),
+    ])
+    .await;
+
+    assert_series("Keltner middle", &batch.values("kc"), &KELTNER_10_5_2);
+    assert_series(
+        "Keltner upper",
+        &batch.values("kc_upper"),
+        &KELTNER_10_5_2_UPPER,
+    );
+    assert_series(
+        "Keltner lower",
+        &batch.values("kc_lower"),
+        &KELTNER_10_5_2_LOWER,
+    );
+}
+
+#[async_std::test]
+async fn donchian_channels_span_the_rolling_range() {
+    let batch = bar_outputs(&[("type", "DONCHIAN"), ("period", "10"), ("as", "dc")]).await;
+    let middle: Vec<_> = DONCHIAN_10_UPPER
+        .iter()
+        .zip(DONCHIAN_10_LOWER)
+        .map(|(upper, lower)| Some(((*upper)? + lower?) / 2.0))
+        .collect();
+
+    assert_series(
+        "Donchian upper",
+        &batch.values("dc_upper"),
+        &DONCHIAN_10_UPPER,
+    );
+    assert_series(
+        "Donchian lower",
+        &batch.values("dc_lower"),
+        &DONCHIAN_10_LOWER,
+    );
+    assert_series("Donchian middle", &batch.values("dc"), &middle);
+}
+
+#[async_std::test]
+async fn momentum_oscillators_on_a_single_field() {
+    let cases: [(&str, &str, &[Option<f64>; 30]); 3] = [
+        ("ROC", "10", &ROC_10),
+        ("MOM", "10", &MOM_10),
+        ("TRIX", "5", &TRIX_5),
+    ];
+
+    for (indicator_type, period, expected) in cases {
+        let batch = close_outputs(&[("type", indicator_type), ("period", period)]).await;
+        assert_series(indicator_type, &batch.values("indicator"), expected);
+    }
+}
+
+#[async_std::test]
+async fn cci_matches_talib() {
+    let batch = bar_outputs(&[("type", "CCI"), ("period", "10")]).await;
+
+    assert_series("CCI", &batch.values("indicator"), &CCI_10);
+}
+
+#[async_std::test]
+async fn ultimate_oscillator_matches_talib() {
+    let batch = bar_outputs(&[("type", "ULTOSC"), ("period", "3")]).await;
+    assert_series("ULTOSC", &batch.values("indicator"), &ULTOSC_3_6_12);
+
+    // Explicit periods are sorted like TA-Lib does
+    let batch = bar_outputs(&[
+        ("type", "ULTOSC"),
+        ("period", "12"),
+        ("medium_period", "3"),
+        ("long_period", "6"),
+    ])
+    .await;
+    assert_series("ULTOSC", &batch.values("indicator"), &ULTOSC_3_6_12);
+}
+
+#[async_std::test]
+async fn ichimoku_plots_spans_displaced() {
+    let batch = bar_outputs(&[
+        ("type", "ICHIMOKU"),
+        ("period", "3"),
+        ("medium_period", "6"),
+        ("long_period", "12"),
+        ("as", "ichimoku"),
+    ])
+    .await;
+    let days: Vec<_> = batch.points.iter().map(|p| p.time).collect();
+
+    assert_eq!(days, ICHIMOKU_DAYS);
+    assert_series("Tenkan", &batch.values("ichimoku_tenkan"), &TENKAN_3);
+    assert_series("Kijun", &batch.values("ichimoku_kijun"), &KIJUN_6);
+    assert_series(
+        "Senkou A",
+        &batch.values("ichimoku_senkou_a"),
+        &SENKOU_A_3_6,
+    );
+    assert_series("Senkou B", &batch.values("ichimoku_senkou_b"), &SENKOU_B_12);
+    assert_series("Chikou", &batch.values("ichimoku_chikou"), &CHIKOU);
+}
+
+#[async_std::test]
+async fn rolling_statistics() {
+    let cases: [(&str, &[Option<f64>; 30]); 7] = [
+        ("STDDEV", &STDDEV_10),
+        ("VARIANCE", &VARIANCE_10),
+        ("ZSCORE", &ZSCORE_10),
+        ("PERCENTRANK", &PERCENTRANK_10),
+        ("MIN", &MIN_10),
+        ("MAX", &MAX_10),
+        ("MEDIAN", &MEDIAN_10),
+    ];
+
+    for (indicator_type, expected) in cases {
+        let batch = close_outputs(&[("type", indicator_type), ("period", "10")]).await;
+        assert_series(indicator_type, &batch.values("indicator"), expected);
+    }
+}
+
+#[async_std::test]
+async fn returns_and_realized_volatility() {
+    let cases: [(&str, &[Option<f64>; 30]); 3] = [
+        ("RETURN", &RETURN_10),
+        ("LOGRETURN", &LOGRETURN_10),
+        ("REALIZEDVOL", &REALIZEDVOL_10),
+    ];
+
+    for (indicator_type, expected) in cases {
+        let batch = close_outputs(&[("type", indicator_type), ("period", "10")]).await;
+        assert_series(indicator_type, &batch.values("indicator"), expected);
+    }
+}
+
+#[async_std::test]
+async fn range_volatility() {
+    let batch = bar_outputs(&[("type", "PARKINSON"), ("period", "10")]).await;
+    assert_series("PARKINSON", &batch.values("indicator"), &PARKINSON_10);
+
+    let mut harness = HandlerHarness::new().await;
+    let init = harness
+        .init(&[("type", "GARMANKLASS"), ("period", "10")])
+        .await
+        .unwrap();
+    assert!(init.success, "init failed: {}", init.error);
+    let points: Vec<_> = BARS
+        .iter()
+        .enumerate()
+        .map(|(day, (high, low, close))| {
+            let open = BARS[day.saturating_sub(1)].2;
+            let mut point = bar(day as i64, "CS", *high, *low, *close);
+            point.fields_double.insert("open".to_string(), open);
+            point
+        })
+        .collect();
+    let batch = harness.batch(&points).await.unwrap().remove(0);
+    assert_series("GARMANKLASS", &batch.values("indicator"), &GARMANKLASS_10);
+}
+
+#[async_std::test]
+async fn linear_regression_matches_talib() {
+    let batch = close_outputs(&[("type", "LINREG"), ("period", "10"), ("as", "lr")]).await;
+
+    assert_series("LINEARREG", &batch.values("lr"), &LINREG_10);
+    assert_series(
+        "LINEARREG_SLOPE",
+        &batch.values("lr_slope"),
+        &LINREG_10_SLOPE,
+    );
+    assert_series(
+        "LINEARREG_INTERCEPT",
+        &batch.values("lr_intercept"),
+        &LINREG_10_INTERCEPT,
+    );
+    assert_series("R2", &batch.values("lr_r2"), &LINREG_10_R2);
+    assert_series("TSF", &batch.values("lr_forecast"), &TSF_10);
+}
@@ -0,0 +1,898 @@
+use kapacitor_multi_indicator_batch_udf::handler::{
+    indicator_handler::IndicatorHandler,
+    state_store::{FileStateStore, StateStore},
+};
+use kapacitor_multi_indicator_batch_udf::harness::{bar, point, BatchOutput, HandlerHarness};
+use kapacitor_udf::proto::Point;
+use std::sync::Arc;
+
+const EPSILON: f64 = 1e-9;
+
+/// A table-driven test case: options, input and the expected indicator values.
+struct Case<I> {
+    name: &'static str,
+    options: &'static [(&'static str, &'static str)],
+    input: I,
+    expected: &'static [f64],
+}
+
+/// Feeds `points` as one batch into a handler initialized with `options` and
+/// returns the `indicator` values of the forwarded points.
+async fn run(options: &[(&str, &str)], points: &[Point]) -> Vec<Option<f64>> {
+    let mut harness = HandlerHarness::new().await;
+    let init = harness.init(options).await.unwrap();
+    assert!(init.success, "init failed: {}", init.error);
+
+    let batches = harness.batch(points).await.unwrap();
+    assert_eq!(batches.len(), 1);
+    batches[0].values("indicator")
+}
+
+fn assert_values(name: &str, actual: &[Option<f64>], expected: &[f64]) {
+    assert_eq!(actual.len(), expected.len(), "{}: {:?}", name, actual);
+    for (i, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+        let actual = actual.unwrap_or_else(|| panic!("{}: point {} has no value", name, i));
+        assert!(
+            (actual - expected).abs() < EPSILON,
+            "{}: point {} is {}, expected {}",
+            name,
+            i,
+            actual,
+            expected
+        );
+    }
+}
+
+fn series(ticker: &str, values: &[f64]) -> Vec<Point> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| point(i as i64 + 1, ticker, *v))
+        .collect()
+}
+
+#[async_std::test]
+async fn indicator_values() {
+    let cases: Vec<Case<&[f64]>> = vec![
+        Case {
+            name: "ema seeds with the first value",
+            options: &[("period", "3")],
+            input: &[1.0, 2.0, 3.0, 4.0],
+            expected: &[1.0, 1.5, 2.25, 3.125],
+        },
+        Case {
+            name: "sma passes values through during warm-up",
+            options: &[("type", "SMA"), ("period", "3")],
+            input: &[1.0, 2.0, 3.0, 4.0, 5.0],
+            expected: &[1.0, 2.0, 2.0, 3.0, 4.0],
+        },
+        Case {
+            name: "sma type is case-insensitive",
+            options: &[("type", "sma"), ("period", "2")],
+            input: &[2.0, 4.0, 8.0],
+            expected: &[2.0, 3.0, 6.0],
+        },
+        Case {
+            name: "period 1 follows the input",
+            options: &[("period", "1")],
+            input: &[5.0, 7.0, 6.0],
+            expected: &[5.0, 7.0, 6.0],
+        },
+    ];
+
+    for case in cases {
+        let values = run(case.options, &series("AAPL", case.input)).await;
+        assert_values(case.name, &values, case.expected);
+    }
+}
+
+#[async_std::test]
+async fn batch_handling() {
+    let cases: Vec<Case<Vec<Point>>> = vec![
+        Case {
+            name: "points are processed in time order",
+            options: &[("period", "3")],
+            input: vec![point(2, "A", 2.0), point(1, "A", 1.0), point(3, "A", 3.0)],
+            expected: &[1.0, 1.5, 2.25],
+        },
+        Case {
+            name: "the last of duplicate points wins",
+            options: &[("period", "3")],
+            input: vec![point(1, "A", 9.0), point(1, "A", 1.0), point(2, "A", 2.0)],
+            expected: &[1.0, 1.5],
+        },
+        Case {
+            name: "tickers keep separate state",
+            options: &[("period", "3")],
+            input: vec![point(1, "A", 1.0), point(1, "B", 10.0), point(2, "A", 3.0)],
+            expected: &[1.0, 10.0, 2.0],
+        },
+        Case {
+            name: "points without ticker are dropped",
+            options: &[("period", "3")],
+            input: vec![
+                point(1, "A", 1.0),
+                Point {
+                    time: 2,
+                    ..Default::default()
+                },
+            ],
+            expected: &[1.0],
+        },
+    ];
+
+    for case in cases {
+        let values = run(case.options, &case.input).await;
+        assert_values(case.name, &values, case.expected);
+    }
+}
+
+#[async_std::test]
+async fn state_carries_over_between_batches() {
+    let mut harness = HandlerHarness::new().await;
+    harness.init(&[("period", "3")]).await.unwrap();
+
+    harness.batch(&[point(1, "A", 1.0)]).await.unwrap();
+    let batches = harness.batch(&[point(2, "A", 2.0)]).await.unwrap();
+
+    assert_values("second batch", &batches[0].values("indicator"), &[1.5]);
+}
+
+#[async_std::test]
+async fn forwarded_batch_reports_emitted_size() {
+    let mut harness = HandlerHarness::new().await;
+    harness.init(&[]).await.unwrap();
+
+    let batches = harness
+        .batch(&[point(1, "A", 1.0), point(1, "A", 2.0), point(2, "A", 3.0)])
+        .await
+        .unwrap();
+
+    assert_eq!(batches[0].begin.size, 2);
+    assert_eq!(batches[0].points.len(), 2);
+    assert_eq!(batches[0].end.as_ref().map(|e| e.tmax), Some(2));
+}
+
+#[async_std::test]
+async fn overflow_stream_forwards_chunks_as_batches() {
+    for (late_policy, times) in [
+        ("drop", vec![vec![11, 12], vec![]]),
+        ("recompute", vec![vec![11, 12], vec![9, 10]]),
+    ] {
+        let mut harness = HandlerHarness::new().await;
+        harness
+            .init(&[
+                ("max_batch_points", "2"),
+                ("overflow_policy", "stream"),
+                ("late_policy", late_policy),
+            ])
+            .await
+            .unwrap();
+
+        let batches = harness
+            .batch(&[
+                point(12, "A", 1.0),
+                point(11, "A", 2.0),
+                point(9, "A", 3.0),
+                point(10, "A", 4.0),
+            ])
+            .await
+            .unwrap();
+
+        // Points older than those of the first chunk are late in the second one
+        let actual: Vec<Vec<i64>> = batches
+            .iter()
+            .map(|b| b.points.iter().map(|p| p.time).collect())
+            .collect();
+        assert_eq!(actual, times, "{}", late_policy);
+        for batch in &batches {
+            assert_eq!(
+                batch.begin.size,
+                batch.points.len() as i64,
+                "{}",
+                late_policy
+            );
+        }
+        assert_eq!(batches[0].end.as_ref().map(|e| e.tmax), Some(12));
+    }
+}
+
+#[async_std::test]
+async fn overflow_truncate_keeps_first_points() {
+    let mut harness = HandlerHarness::new().await;
+    harness
+        .init(&[("max_batch_points", "2"), ("overflow_policy", "truncate")])
+        .await
+        .unwrap();
+
+    let batches = harness
+        .batch(&[
+            point(1, "A", 1.0),
+            point(2, "A", 2.0),
+            point(3, "A", 3.0),
+            point(4, "A", 4.0),
+        ])
+        .await
+        .unwrap();
+
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].begin.size, 2);
+    let times: Vec<_> = batches[0].points.iter().map(|p| p.time).collect();
+    assert_eq!(times, [1, 2]);
+}
+
+#[async_std::test]
+async fn overflow_error_fails_batch() {
+    let mut harness = HandlerHarness::new().await;
+    harness.init(&[("max_batch_points", "2")]).await.unwrap();
+
+    let result = harness
+        .batch(&[point(1, "A", 1.0), point(2, "A", 2.0), point(3, "A", 3.0)])
+        .await;
+
+    assert!(result.is_err());
+}
+
+/// Two valid points around one without the ticker tag and one without the field.
+fn batch_with_rejections() -> Vec<Point> {
+    let mut no_ticker = point(2, "A", 2.0);
+    no_ticker.tags.clear();
+    let mut no_field = point(3, "A", 3.0);
+    no_field.fields_double.clear();
+    vec![point(1, "A", 1.0), no_ticker, no_field, point(4, "A", 4.0)]
+}
+
+#[async_std::test]
+async fn reject_pass_marks_rejected_points() {
+    let mut harness = HandlerHarness::new().await;
+    harness
+        .init(&[("period", "3"), ("on_reject", "pass")])
+        .await
+        .unwrap();
+
+    let batches = harness.batch(&batch_with_rejections()).await.unwrap();
+
+    let errors: Vec<_> = batches[0]
+        .points
+        .iter()
+        .map(|p| p.fields_string.get("indicator_error").map(String::as_str))
+        .collect();
+    assert_eq!(
+        errors,
+        [None, Some("missing_ticker"), Some("missing_field"), None]
+    );
+    assert_eq!(
+        batches[0].values("indicator"),
+        [Some(1.0), None, None, Some(2.5)]
+    );
+}
+
+#[async_std::test]
+async fn reject_summary_counts_rejected_points() {
+    let mut harness = HandlerHarness::new().await;
+    harness.init(&[("on_reject", "summary")]).await.unwrap();
+
+    let batches = harness.batch(&batch_with_rejections()).await.unwrap();
+
+    let points = &batches[0].points;
+    assert_eq!(points.len(), 3);
+    let summary = &points[2];
+    assert_eq!(summary.time, 4);
+    assert_eq!(summary.fields_int.get("indicator_missing_ticker"), Some(&1));
+    assert_eq!(summary.fields_int.get("indicator_missing_field"), Some(&1));
+    assert_eq!(batches[0].begin.size, 3);
+}
+
+#[async_std::test]
+async fn reject_summary_of_empty_batch_is_suppressed() {
+    let mut harness = HandlerHarness::new().await;
+    harness
+        .init(&[("on_reject", "summary"), ("suppress_empty", "true")])
+        .await
+        .unwrap();
+
+    assert!(harness.batch(&[]).await.unwrap().is_empty());
+
+    // A batch of only rejected points still reports them
+    let mut no_field = point(1, "A", 1.0);
+    no_field.fields_double.clear();
+    let batches = harness.batch(&[no_field]).await.unwrap();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(
+        batches[0].points[0]
+            .fields_int
+            .get("indicator_missing_field"),
+        Some(&1)
+    );
+}
+
+#[async_std::test]
+async fn empty_batches() {
+    for (suppress_empty, forwarded) in [("false", 1), ("true", 0)] {
+        let mut harness = HandlerHarness::new().await;
+        harness
+            .init(&[("suppress_empty", suppress_empty)])
+            .await
+            .unwrap();
+
+        let batches = harness.batch(&[]).await.unwrap();
+
+        assert_eq!(
+            batches.len(),
+            forwarded,
+            "suppress_empty={}",
+            suppress_empty
+        );
+    }
+}
+
+#[async_std::test]
+async fn max_series_evicts_least_recently_updated() {
+    let mut harness = HandlerHarness::new().await;
+    harness
+        .init(&[("period", "3"), ("max_series", "1")])
+        .await
+        .unwrap();
+
+    // C arrives before B in the batch but was last updated before it
+    harness
+        .batch(&[point(1, "A", 1.0), point(3, "B", 1.0), point(2, "C", 1.0)])
+        .await
+        .unwrap();
+    let batches = harness
+        .batch(&[point(4, "A", 5.0), point(5, "B", 5.0), point(6, "C", 5.0)])
+        .await
+        .unwrap();
+
+    // A and C start over from their first value, B continues its EMA
+    assert_values(
+        "max_series",
+        &batches[0].values("indicator"),
+        &[5.0, 3.0, 5.0],
+    );
+}
+
+#[async_std::test]
+async fn series_ttl_evicts_idle_tickers() {
+    let mut harness = HandlerHarness::new().await;
+    harness
+        .init(&[("period", "3"), ("series_ttl", "10s")])
+        .await
+        .unwrap();
+    let second = 1_000_000_000;
+
+    harness
+        .batch(&[
+            point(0, "A", 1.0),
+            point(5 * second, "B", 1.0),
+            point(20 * second, "C", 1.0),
+        ])
+        .await
+        .unwrap();
+    let batches = harness
+        .batch(&[
+            point(21 * second, "A", 5.0),
+            point(22 * second, "B", 5.0),
+            point(23 * second, "C", 5.0),
+        ])
+        .await
+        .unwrap();
+
+    // A and B were idle for more than 10s when the first batch ended
+    assert_values(
+        "series_ttl",
+        &batches[0].values("indicator"),
+        &[5.0, 5.0, 3.0],
+    );
+}
+
+#[async_std::test]
+async fn gap_policies() {
+    // Points 1ns apart, then a gap of 3ns or of more intervals than are ever filled
+    let cases: [(&str, i64, f64); 4] = [
+        ("reset", 5, 8.0),
+        ("carry", 5, 5.0),
+        // The two missing intervals filled with 4.0 take the EMA from 2.0 to 3.5
+        ("fill", 5, 5.75),
+        ("fill", 200_003, 8.0),
+    ];
+
+    for (gap_policy, time, expected) in cases {
+        let mut harness = HandlerHarness::new().await;
+        harness
+            .init(&[
+                ("period", "3"),
+                ("max_gap", "1"),
+                ("gap_policy", gap_policy),
+            ])
+            .await
+            .unwrap();
+        let batches = harness
+            .batch(&[
+                point(0, "A", 0.0),
+                point(1, "A", 0.0),
+                point(2, "A", 4.0),
+                point(time, "A", 8.0),
+            ])
+            .await
+            .unwrap();
+
+        let name = format!("{} at {}", gap_policy, time);
+        assert_values(
+            &name,
+            &batches[0].values("indicator"),
+            &[0.0, 0.0, 2.0, expected],
+        );
+    }
+}
+
+#[async_std::test]
+async fn late_point_under_warn_keeps_last_time() {
+    let mut harness = HandlerHarness::new().await;
+    harness
+        .init(&[("period", "2"), ("late_policy", "warn"), ("max_gap", "15")])
+        .await
+        .unwrap();
+
+    harness
+        .batch(&[point(10, "A", 1.0), point(20, "A", 4.0)])
+        .await
+        .unwrap();
+    let late = harness.batch(&[point(5, "A", 10.0)]).await.unwrap();
+    let batches = harness.batch(&[point(30, "A", 7.0)]).await.unwrap();
+
+    // The late point is applied out of order, but the gap to 30 is still measured from 20
+    assert_values("late", &late[0].values("indicator"), &[23.0 / 3.0]);
+    assert_values("after late", &batches[0].values("indicator"), &[65.0 / 9.0]);
+}
+
+#[async_std::test]
+async fn late_point_under_warn_is_processed_once() {
+    let mut harness = HandlerHarness::new().await;
+    harness
+        .init(&[("period", "2"), ("late_policy", "warn")])
+        .await
+        .unwrap();
+
+    harness
+        .batch(&[point(10, "A", 1.0), point(20, "A", 4.0)])
+        .await
+        .unwrap();
+    let late = harness.batch(&[point(5, "A", 10.0)]).await.unwrap();
+    let redelivered = harness.batch(&[point(5, "A", 10.0)]).await.unwrap();
+    let batches = harness.batch(&[point(30, "A", 7.0)]).await.unwrap();
+
+    assert_values("late", &late[0].values("indicator"), &[23.0 / 3.0]);
+    assert!(redelivered[0].points.is_empty());
+    assert_values("after late", &batches[0].values("indicator"), &[65.0 / 9.0]);
+}
+
+#[async_std::test]
+async fn late_point_under_recompute_is_replayed_in_order() {
+    let mut harness = HandlerHarness::new().await;
+    harness
+        .init(&[("period", "2"), ("late_policy", "recompute")])
+        .await
+        .unwrap();
+
+    harness
+        .batch(&[point(10, "A", 1.0), point(30, "A", 4.0)])
+        .await
+        .unwrap();
+    let late = harness.batch(&[point(20, "A", 10.0)]).await.unwrap();
+    let batches = harness.batch(&[point(40, "A", 8.0)]).await.unwrap();
+
+    // The EMA is 7.0 at 20 and 5.0 at 30 with the late point in place
+    assert_values("late", &late[0].values("indicator"), &[7.0]);
+    assert_values("after late", &batches[0].values("indicator"), &[7.0]);
+}
+
+#[async_std::test]
+async fn late_point_under_drop_is_dropped() {
+    let mut harness = HandlerHarness::new().await;
+    harness.init(&[("period", "2")]).await.unwrap();
+
+    harness
+        .batch(&[point(10, "A", 1.0), point(30, "A", 4.0)])
+        .await
+        .unwrap();
+    let late = harness.batch(&[point(20, "A", 10.0)]).await.unwrap();
+    let batches = harness.batch(&[point(40, "A", 8.0)]).await.unwrap();
+
+    assert!(late[0].points.is_empty());
+    assert_values("after late", &batches[0].values("indicator"), &[19.0 / 3.0]);
+}
+
+#[async_std::test]
+async fn shifted_outputs_use_the_bar_interval() {
+    let mut harness = HandlerHarness::new().await;
+    harness
+        .init(&[
+            ("type", "ICHIMOKU"),
+            ("period", "1"),
+            ("medium_period", "1"),
+            ("long_period", "1"),
+            ("displacement", "2"),
+        ])
+        .await
+        .unwrap();
+    let times = |batch: &BatchOutput, field: &str| -> Vec<i64> {
+        batch
+            .points
+            .iter()
+            .filter(|p| p.fields_double.contains_key(field))
+            .map(|p| p.time)
+            .collect()
+    };
+
+    // The interval is the smallest spacing in the batch, not the gap before the last point
+    let batches = harness
+        .batch(&[
+            bar(0, "A", 2.0, 1.0, 1.5),
+            bar(60, "A", 2.0, 1.0, 1.5),
+            bar(120, "A", 2.0, 1.0, 1.5),
+            bar(300, "A", 2.0, 1.0, 1.5),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(
+        times(&batches[0], "indicator_senkou_b"),
+        [120, 180, 240, 420]
+    );
+    assert_eq!(times(&batches[0], "indicator_chikou"), [-120, -60, 0, 180]);
+
+    // A single point falls back to the spacing of the ticker's last two points
+    let batches = harness
+        .batch(&[bar(360, "A", 2.0, 1.0, 1.5)])
+        .await
+        .unwrap();
+    assert_eq!(times(&batches[0], "indicator_senkou_b"), [480]);
+}
+
+#[async_std::test]
+async fn snapshot_restores_state() {
+    for format in ["json", "binary"] {
+        let options = [("period", "3"), ("snapshot_format", format)];
+        let mut original = HandlerHarness::new().await;
+        original.init(&options).await.unwrap();
+        original.batch(&[point(1, "A", 1.0)]).await.unwrap();
+        let snapshot = original.snapshot().await.unwrap();
+
+        let mut restored = HandlerHarness::new().await;
+        restored.init(&options).await.unwrap();
+        let response = restored.restore(&snapshot).await.unwrap();
+        assert!(response.success, "{}: {}", format, response.error);
+
+        let batches = restored.batch(&[point(2, "A", 2.0)]).await.unwrap();
+        assert_values(format, &batches[0].values("indicator"), &[1.5]);
+    }
+}
+
+#[async_std::test]
+async fn snapshot_restores_indicator_detail_state() {
+    let options = [("type", "ADX"), ("period", "2")];
+    let bars: Vec<_> = (0..6)
+        .map(|i| {
+            let close = [10.0, 11.0, 10.5, 12.0, 11.0, 13.0][i];
+            bar(i as i64, "A", close + 0.5, close - 0.5, close)
+        })
+        .collect();
+
+    let mut uninterrupted = HandlerHarness::new().await;
+    uninterrupted.init(&options).await.unwrap();
+    let expected = uninterrupted.batch(&bars).await.unwrap();
+
+    let mut original = HandlerHarness::new().await;
+    original.init(&options).await.unwrap();
+    original.batch(&bars[..3]).await.unwrap();
+    let snapshot = original.snapshot().await.unwrap();
+
+    let mut restored = HandlerHarness::new().await;
+    restored.init(&options).await.unwrap();
+    assert!(restored.restore(&snapshot).await.unwrap().success);
+    let batches = restored.batch(&bars[3..]).await.unwrap();
+
+    assert_eq!(batches[0].points, expected[0].points[3..]);
+}
+
+#[async_std::test]
+async fn drawdown_peak_survives_batches_and_restore() {
+    let options = [("type", "DRAWDOWN"), ("as", "dd")];
+    let mut original = HandlerHarness::new().await;
+    original.init(&options).await.unwrap();
+    original
+        .batch(&[point(1, "A", 100.0), point(2, "A", 120.0)])
+        .await
+        .unwrap();
+    let snapshot = original.snapshot().await.unwrap();
+
+    let mut restored = HandlerHarness::new().await;
+    restored.init(&options).await.unwrap();
+    assert!(restored.restore(&snapshot).await.unwrap().success);
+    let batches = restored
+        .batch(&[
+            point(3, "A", 90.0),
+            point(4, "A", 105.0),
+            point(5, "A", 130.0),
+        ])
+        .await
+        .unwrap();
+
+    let batch = &batches[0];
+    assert_eq!(batch.values("dd"), [Some(0.25), Some(0.125), Some(0.0)]);
+    assert_eq!(
+        batch.values("dd_peak"),
+        [Some(120.0), Some(120.0), Some(130.0)]
+    );
+    assert_eq!(batch.values("dd_max"), [Some(0.25), Some(0.25), Some(0.25)]);
+    assert_eq!(batch.int_values("dd_duration"), [Some(1), Some(2), Some(0)]);
+}
+
+#[async_std::test]
+async fn linear_regression_on_time() {
+    let mut harness = HandlerHarness::new().await;
+    harness
+        .init(&[
+            ("type", "LINREG"),
+            ("period", "3"),
+            ("regression_x", "time"),
+            ("forecast", "2"),
+            ("as", "lr"),
+        ])
+        .await
+        .unwrap();
+    let second = 1_000_000_000;
+    let batches = harness
+        .batch(&[
+            point(0, "A", 1.0),
+            point(second, "A", 2.0),
+            point(3 * second, "A", 4.0),
+        ])
+        .await
+        .unwrap();
+
+    // y = 1 + t on irregularly spaced points, projected two average spacings of 1.5s ahead
+    let batch = &batches[0];
+    for (field, expected) in [
+        ("lr", 4.0),
+        ("lr_slope", 1.0),
+        ("lr_intercept", 1.0),
+        ("lr_r2", 1.0),
+        ("lr_forecast", 7.0),
+    ] {
+        let values = batch.values(field);
+        assert_eq!(values[..2], [None, None], "{}", field);
+        assert!((values[2].unwrap() - expected).abs() < EPSILON, "{}", field);
+    }
+}
+
+#[async_std::test]
+async fn signals_threshold_crossings_across_batches() {
+    let options = [
+        ("type", "SMA"),
+        ("period", "1"),
+        ("signal_on", "indicator"),
+        ("signal_threshold", "70"),
+    ];
+    let mut original = HandlerHarness::new().await;
+    original.init(&options).await.unwrap();
+    let first = original
+        .batch(&[point(1, "A", 60.0), point(2, "A", 75.0)])
+        .await
+        .unwrap();
+    let snapshot = original.snapshot().await.unwrap();
+
+    let mut restored = HandlerHarness::new().await;
+    restored.init(&options).await.unwrap();
+    assert!(restored.restore(&snapshot).await.unwrap().success);
+    let second = restored
+        .batch(&[
+            point(3, "A", 80.0),
+            point(4, "A", 70.0),
+            point(5, "A", 65.0),
+            point(6, "A", 71.0),
+        ])
+        .await
+        .unwrap();
+
+    let signals = |batch: &BatchOutput| -> Vec<Option<String>> {
+        batch
+            .points
+            .iter()
+            .map(|p| p.fields_string.get("indicator_signal").cloned())
+            .collect()
+    };
+    assert_eq!(signals(&first[0]), [None, Some("cross_up".to_string())]);
+    // Touching the threshold does not signal until the value moves to the other side
+    assert_eq!(
+        signals(&second[0]),
+        [
+            None,
+            None,
+            Some("cross_down".to_string()),
+            Some("cross_up".to_string())
+        ]
+    );
+}
+
+#[async_std::test]
+async fn restore_with_another_signal_threshold_forgets_the_signal_side() {
+    let options = |threshold| {
+        [
+            ("type", "SMA"),
+            ("period", "2"),
+            ("signal_on", "indicator"),
+            ("signal_threshold", threshold),
+        ]
+    };
+    let mut original = HandlerHarness::new().await;
+    original.init(&options("60")).await.unwrap();
+    original
+        .batch(&[point(1, "A", 60.0), point(2, "A", 75.0)])
+        .await
+        .unwrap();
+    let snapshot = original.snapshot().await.unwrap();
+
+    let mut restored = HandlerHarness::new().await;
+    restored.init(&options("80")).await.unwrap();
+    assert!(restored.restore(&snapshot).await.unwrap().success);
+    let batches = restored
+        .batch(&[point(3, "A", 80.0), point(4, "A", 100.0)])
+        .await
+        .unwrap();
+
+    // The SMA continues, but being below 80 is not taken for crossing down from above 60
+    assert_values("SMA", &batches[0].values("indicator"), &[77.5, 90.0]);
+    let signals: Vec<_> = batches[0]
+        .points
+        .iter()
+        .map(|p| p.fields_string.get("indicator_signal").map(String::as_str))
+        .collect();
+    assert_eq!(signals, [None, Some("cross_up")]);
+}
+
+#[async_std::test]
+async fn signals_crossing_of_another_field_as_tag() {
+    let mut harness = HandlerHarness::new().await;
+    harness
+        .init(&[
+            ("type", "SMA"),
+            ("period", "1"),
+            ("as", "fast"),
+            ("signal_on", "fast"),
+            ("signal_cross", "slow"),
+            ("signal_tag", "true"),
+        ])
+        .await
+        .unwrap();
+    let points: Vec<_> = [(1.0, 2.0), (3.0, 2.0), (1.0, 2.0)]
+        .iter()
+        .enumerate()
+        .map(|(i, &(fast, slow))| {
+            let mut point = point(i as i64, "A", fast);
+            point.fields_double.insert("slow".to_string(), slow);
+            point
+        })
+        .collect();
+    let batches = harness.batch(&points).await.unwrap();
+
+    let signals: Vec<_> = batches[0]
+        .points
+        .iter()
+        .map(|p| p.tags.get("fast_signal").map(String::as_str))
+        .collect();
+    assert_eq!(signals, [None, Some("cross_up"), Some("cross_down")]);
+}
+
+#[async_std::test]
+async fn restore_rejects_other_indicator() {
+    let mut ema = HandlerHarness::new().await;
+    ema.init(&[]).await.unwrap();
+    let snapshot = ema.snapshot().await.unwrap();
+
+    let mut sma = HandlerHarness::new().await;
+    sma.init(&[("type", "SMA")]).await.unwrap();
+    let response = sma.restore(&snapshot).await.unwrap();
+
+    assert!(!response.success);
+}
+
+#[async_std::test]
+async fn state_store_discards_state_of_changed_options() {
+    let dir = std::env::temp_dir().join(format!("kmib-state-store-{}", std::process::id()));
+    let store: Arc<dyn StateStore> = Arc::new(FileStateStore::new(dir.clone()));
+    let with_store = |store: &Arc<dyn StateStore>| {
+        let store = Arc::clone(store);
+        move |handler: IndicatorHandler| handler.with_state_store(store)
+    };
+
+    let mut original = HandlerHarness::new().await.map_handler(with_store(&store));
+    original.init(&[("period", "2")]).await.unwrap();
+    original
+        .batch(&[point(1, "A", 10.0), point(2, "A", 20.0)])
+        .await
+        .unwrap();
+
+    // A restart with the same options continues the EMA from the stored state
+    let mut restarted = HandlerHarness::new().await.map_handler(with_store(&store));
+    restarted.init(&[("period", "2")]).await.unwrap();
+    let batches = restarted.batch(&[point(3, "A", 30.0)]).await.unwrap();
+    assert_values(
+        "same options",
+        &batches[0].values("indicator"),
+        &[230.0 / 9.0],
+    );
+
+    // A restart with another period starts over instead of reading that state
+    let mut changed = HandlerHarness::new().await.map_handler(with_store(&store));
+    changed.init(&[("period", "3")]).await.unwrap();
+    let batches = changed.batch(&[point(3, "A", 30.0)]).await.unwrap();
+    assert_values("changed period", &batches[0].values("indicator"), &[30.0]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[async_std::test]
+async fn file_state_store_saves_many_tickers_at_once() {
+    let dir = std::env::temp_dir().join(format!("kmib-save-many-{}", std::process::id()));
+    let store = FileStateStore::new(dir.clone());
+    let states = vec![
+        ("A".to_string(), b"a".to_vec()),
+        ("B/C".to_string(), b"bc".to_vec()),
+        ("D".to_string(), b"d".to_vec()),
+    ];
+
+    store.save_many("task", &[]).await.unwrap();
+    store.save_many("task", &states).await.unwrap();
+    store.save("task", "D", b"e").await.unwrap();
+
+    let loaded = store.load("task").await.unwrap();
+    assert_eq!(loaded.len(), 3);
+    assert_eq!(loaded["A"], b"a");
+    assert_eq!(loaded["B/C"], b"bc");
+    assert_eq!(loaded["D"], b"e");
+    let leftovers: Vec<_> = std::fs::read_dir(dir.join("task"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .filter(|name| name.to_string_lossy().ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[async_std::test]
+async fn invalid_options_fail_init() {
+    let cases: &[(&str, &str)] = &[
+        ("type", "MACD"),
+        ("gap_policy", "interpolate"),
+        ("max_series", "-1"),
+        ("period", "0"),
+        ("multiplier", "-1"),
+        ("acceleration", "0.5"),
+    ];
+
+    for (name, value) in cases {
+        let mut harness = HandlerHarness::new().await;
+        let response = harness.init(&[(name, value)]).await.unwrap();
+        assert!(!response.success, "{}={} was accepted", name, value);
+    }
+}
+
+#[async_std::test]
+async fn info_lists_all_options() {
+    let harness = HandlerHarness::new().await;
+    let info = harness.info().await.unwrap();
+
+    for option in [
+        "type",
+        "period",
+        "field",
+        "as",
+        "ticker_field",
+        "high_field",
+        "on_reject",
+    ] {
+        assert!(info.options.contains_key(option), "missing {}", option);
+    }
+}
@@ -0,0 +1,152 @@
+use async_std::{
+    io::{ReadExt, WriteExt},
+    net::TcpStream,
+    task,
+};
+use kapacitor_multi_indicator_batch_udf::{
+    handler::metrics::{self, Metrics},
+    harness::{point, HandlerHarness},
+};
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+async fn harness_with(metrics: &Arc<Metrics>) -> HandlerHarness {
+    let metrics = Arc::clone(metrics);
+    let mut harness = HandlerHarness::new()
+        .await
+        .map_handler(|handler| handler.with_metrics(metrics));
+    harness.init(&[]).await.unwrap();
+    harness
+}
+
+#[async_std::test]
+async fn render_reports_task_counters() {
+    let metrics = Arc::new(Metrics::new());
+    let mut harness = harness_with(&metrics).await;
+    harness
+        .batch(&[point(1, "A", 1.0), point(1, "A", 2.0), point(2, "A", 3.0)])
+        .await
+        .unwrap();
+
+    let rendered = metrics.render();
+    for line in [
+        "# TYPE indicator_udf_batches_total counter",
+        "indicator_udf_active_agents 1",
+        "indicator_udf_batches_total{task=\"harness\"} 1",
+        "indicator_udf_points_received_total{task=\"harness\"} 3",
+        "indicator_udf_points_emitted_total{task=\"harness\"} 2",
+        "indicator_udf_tracked_series{task=\"harness\"} 1",
+        "indicator_udf_points_dropped_total{task=\"harness\",reason=\"duplicate\"} 1",
+        "indicator_udf_compute_seconds_count{indicator=\"EMA\"} 2",
+    ] {
+        assert!(
+            rendered.lines().any(|l| l == line),
+            "missing {:?} in\n{}",
+            line,
+            rendered
+        );
+    }
+}
+
+#[async_std::test]
+async fn task_counters_are_dropped_with_the_last_handler() {
+    let metrics = Arc::new(Metrics::new());
+    let mut first = harness_with(&metrics).await;
+    let second = harness_with(&metrics).await;
+    first.batch(&[point(1, "A", 1.0)]).await.unwrap();
+
+    drop(second);
+    assert!(metrics.render().contains("task=\"harness\""));
+
+    drop(first);
+    let rendered = metrics.render();
+    assert!(!rendered.contains("task=\"harness\""), "{}", rendered);
+    assert!(rendered.contains("indicator_udf_active_agents 0"));
+}
+
+/// Collects the log output of the current thread.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[async_std::test]
+async fn overlapping_batches_count_redelivered_points_as_duplicates() {
+    let logs = Logs::default();
+    let writer = logs.clone();
+    let _guard = tracing::subscriber::set_default(
+        tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::WARN)
+            .with_writer(move || writer.clone())
+            .finish(),
+    );
+    let metrics = Arc::new(Metrics::new());
+    let mut harness = harness_with(&metrics).await;
+
+    let first = [point(1, "A", 1.0), point(2, "A", 2.0), point(3, "A", 3.0)];
+    harness.batch(&first).await.unwrap();
+    let batches = harness.batch(&first).await.unwrap();
+
+    assert!(batches[0].points.is_empty());
+    let rendered = metrics.render();
+    assert!(
+        rendered
+            .lines()
+            .any(|l| l
+                == "indicator_udf_points_dropped_total{task=\"harness\",reason=\"duplicate\"} 3"),
+        "{}",
+        rendered
+    );
+    assert!(!rendered.contains("reason=\"late\""), "{}", rendered);
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(logs.is_empty(), "{}", logs);
+}
+
+async fn get(port: u16, path: &str) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[async_std::test]
+async fn serve_answers_metrics_requests() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let metrics = Arc::new(Metrics::new());
+    task::spawn(metrics::serve(("127.0.0.1", port), Arc::clone(&metrics)));
+
+    let mut response = String::new();
+    for _ in 0..50 {
+        if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            response = get(port, "/metrics").await;
+            break;
+        }
+        task::sleep(Duration::from_millis(20)).await;
+    }
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with(&metrics.render()));
+    assert!(get(port, "/other")
+        .await
+        .starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
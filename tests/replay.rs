@@ -0,0 +1,160 @@
+use async_std::io::{self, Cursor};
+use kapacitor_multi_indicator_batch_udf::{
+    handler::config::parse_option,
+    harness::{point, HandlerHarness},
+    replay::{recorder::Recorder, Session},
+};
+use kapacitor_udf::{
+    io::{read_message, write_message},
+    proto::{
+        request, response, BeginBatch, EndBatch, InitRequest, Request, Response, SnapshotRequest,
+        SnapshotResponse,
+    },
+};
+
+fn request(message: request::Message) -> Request {
+    Request {
+        message: Some(message),
+    }
+}
+
+/// The requests of a short session: an init, two batches and a snapshot.
+fn requests() -> Vec<Request> {
+    let options = [("period", "3"), ("snapshot_format", "binary")]
+        .iter()
+        .map(|(name, value)| parse_option(name, value).unwrap())
+        .collect();
+    let mut requests = vec![request(request::Message::Init(InitRequest {
+        options,
+        task_id: "replay".to_string(),
+        node_id: "replay".to_string(),
+    }))];
+    for batch in [
+        vec![point(1, "A", 1.0), point(1, "B", 10.0)],
+        vec![point(2, "A", 2.0), point(2, "B", 20.0)],
+    ] {
+        requests.push(request(request::Message::Begin(BeginBatch {
+            name: "harness".to_string(),
+            size: batch.len() as i64,
+            ..Default::default()
+        })));
+        let tmax = batch[0].time;
+        requests.extend(
+            batch
+                .into_iter()
+                .map(|p| request(request::Message::Point(p))),
+        );
+        requests.push(request(request::Message::End(EndBatch {
+            name: "harness".to_string(),
+            tmax,
+            ..Default::default()
+        })));
+    }
+    requests.push(request(request::Message::Snapshot(SnapshotRequest {})));
+    requests
+}
+
+/// Serves the requests with a harness, independently of the replay, and
+/// returns the responses in the order the agent writes them.
+async fn serve(requests: &[Request]) -> Vec<Response> {
+    let mut harness = HandlerHarness::new().await;
+    let mut responses = Vec::new();
+    for request in requests {
+        let message = match request.message.clone().unwrap() {
+            request::Message::Init(init) => Some(response::Message::Init(
+                harness.init_request(&init).await.unwrap(),
+            )),
+            request::Message::Snapshot(_) => Some(response::Message::Snapshot(SnapshotResponse {
+                snapshot: harness.snapshot().await.unwrap(),
+            })),
+            request::Message::Begin(begin) => {
+                harness.begin_batch(&begin).await.unwrap();
+                None
+            }
+            request::Message::Point(point) => {
+                harness.point(&point).await.unwrap();
+                None
+            }
+            request::Message::End(end) => {
+                harness.end_batch(&end).await.unwrap();
+                None
+            }
+            other => panic!("unexpected request {:?}", other),
+        };
+        responses.extend(harness.take_responses());
+        responses.extend(message.map(|message| Response {
+            message: Some(message),
+        }));
+    }
+    responses
+}
+
+#[async_std::test]
+async fn recorded_session_replays_without_mismatches() {
+    let base = std::env::temp_dir().join(format!("kmib-replay-{}", std::process::id()));
+    let requests = requests();
+    let responses = serve(&requests).await;
+
+    // Requests are recorded as the agent reads them, responses as it writes them
+    let mut encoded = Vec::new();
+    for request in &requests {
+        write_message(request, &mut encoded).await.unwrap();
+    }
+    let log = std::fs::File::create(base.with_extension("requests")).unwrap();
+    let mut reader = Recorder::new(Cursor::new(encoded), log);
+    let mut buf = Vec::new();
+    for _ in &requests {
+        let mut request = Request::default();
+        read_message(&mut buf, &mut reader, &mut request)
+            .await
+            .unwrap();
+    }
+    let log = std::fs::File::create(base.with_extension("responses")).unwrap();
+    let mut writer = Recorder::new(io::sink(), log);
+    for response in &responses {
+        write_message(response, &mut writer).await.unwrap();
+    }
+    reader.finish().await;
+    writer.finish().await;
+
+    let session = Session::load(base.with_extension("requests"))
+        .await
+        .unwrap();
+    assert_eq!(session.requests, requests);
+    assert_eq!(session.responses, responses);
+    assert_eq!(session.replay().await, []);
+
+    // A differing response is reported at its position
+    let mut tampered = session.clone();
+    let index = tampered
+        .responses
+        .iter()
+        .position(|r| matches!(r.message, Some(response::Message::Point(_))))
+        .unwrap();
+    if let Some(response::Message::Point(point)) = &mut tampered.responses[index].message {
+        point.fields_double.insert("indicator".to_string(), -1.0);
+    }
+    let mismatches = tampered.replay().await;
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].index, index);
+
+    std::fs::remove_file(base.with_extension("requests")).unwrap();
+    std::fs::remove_file(base.with_extension("responses")).unwrap();
+}
+
+#[async_std::test]
+async fn failing_recording_keeps_the_stream_working() {
+    let path = std::env::temp_dir().join(format!("kmib-read-only-{}", std::process::id()));
+    std::fs::write(&path, b"").unwrap();
+    // Writing to a file opened for reading fails
+    let log = std::fs::File::open(&path).unwrap();
+
+    let mut writer = Recorder::new(io::sink(), log);
+    for request in requests() {
+        write_message(&request, &mut writer).await.unwrap();
+    }
+    writer.finish().await;
+
+    assert!(std::fs::read(&path).unwrap().is_empty());
+    std::fs::remove_file(path).unwrap();
+}